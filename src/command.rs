@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+/// A command typed after `:` in the interface.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Open,
+    /// Open the comments of the selected entry instead of the entry itself.
+    OpenComments,
+    /// Open this many of the newest unread entries, or the configured number.
+    OpenRecent(Option<usize>),
+    MarkRead,
+    Refresh,
+    Reload,
+    Add(String),
+    /// Remind of the selected entry after the duration.
+    Remind(Duration),
+    Unremind,
+    /// Stop fetching the feed of the selected entry.
+    Disable,
+    Enable,
+    /// Hide the selected entry for the duration.
+    Snooze(Duration),
+    /// Hide the selected entry for good, even when read entries are shown.
+    Hide,
+    /// Copy the path of the cached copy of the selected entry's feed.
+    CachePath,
+    /// Open the directory of the cached copies of feeds.
+    OpenCacheDir,
+    Sort(SortBy),
+    /// Take back the last marking read, snoozing or hiding.
+    Undo,
+    Quit,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SortBy {
+    Date,
+    Feed,
+    Title,
+    /// Unread entries above read entries.
+    Unread,
+}
+
+const USAGE: &str = "open, open-comments, open-recent [n], mark-read, refresh, reload, add <url>, \
+                     remind <duration>, unremind, snooze <duration>, hide, cache-path, open-cache-dir, disable, enable, sort date|feed|title|unread, undo, quit";
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<_>>();
+        let command = match (name, &args[..]) {
+            ("open", []) => Command::Open,
+            ("open-comments", []) => Command::OpenComments,
+            ("open-recent", []) => Command::OpenRecent(None),
+            ("open-recent", [n]) => match n.parse() {
+                Ok(n) => Command::OpenRecent(Some(n)),
+                Err(_) => return Err(format!("Invalid number of entries: {}", n)),
+            },
+            ("mark-read", []) => Command::MarkRead,
+            ("refresh", []) => Command::Refresh,
+            ("reload", []) => Command::Reload,
+            ("add", [url]) => Command::Add(url.to_string()),
+            ("remind", [duration]) => match crate::parse_duration(duration) {
+                Ok(duration) => Command::Remind(duration),
+                Err(e) => return Err(e.to_string()),
+            },
+            ("unremind", []) => Command::Unremind,
+            ("hide", []) => Command::Hide,
+            ("cache-path", []) => Command::CachePath,
+            ("open-cache-dir", []) => Command::OpenCacheDir,
+            ("disable", []) => Command::Disable,
+            ("enable", []) => Command::Enable,
+            ("snooze", [duration]) => match crate::parse_duration(duration) {
+                Ok(duration) => Command::Snooze(duration),
+                Err(e) => return Err(e.to_string()),
+            },
+            ("sort", ["date"]) => Command::Sort(SortBy::Date),
+            ("sort", ["feed"]) => Command::Sort(SortBy::Feed),
+            ("sort", ["title"]) => Command::Sort(SortBy::Title),
+            ("sort", ["unread"]) => Command::Sort(SortBy::Unread),
+            ("undo", []) => Command::Undo,
+            ("q", []) | ("quit", []) => Command::Quit,
+            _ => return Err(format!("Unknown command: {} (try {})", line.trim(), USAGE)),
+        };
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Command::parse("open"), Ok(Command::Open));
+        assert_eq!(Command::parse(" mark-read "), Ok(Command::MarkRead));
+        assert_eq!(
+            Command::parse("open-recent 3"),
+            Ok(Command::OpenRecent(Some(3)))
+        );
+        assert!(Command::parse("open-recent many").is_err());
+        assert_eq!(Command::parse("reload"), Ok(Command::Reload));
+        assert_eq!(
+            Command::parse("add https://example.com/feed.xml"),
+            Ok(Command::Add("https://example.com/feed.xml".to_string()))
+        );
+        assert_eq!(
+            Command::parse("sort unread"),
+            Ok(Command::Sort(SortBy::Unread))
+        );
+        assert_eq!(
+            Command::parse("remind 2d"),
+            Ok(Command::Remind(Duration::from_secs(2 * 24 * 60 * 60)))
+        );
+        assert!(Command::parse("remind soon").is_err());
+        assert_eq!(
+            Command::parse("snooze 3h"),
+            Ok(Command::Snooze(Duration::from_secs(3 * 60 * 60)))
+        );
+        assert_eq!(Command::parse("hide"), Ok(Command::Hide));
+        assert_eq!(Command::parse("undo"), Ok(Command::Undo));
+        assert_eq!(Command::parse("q"), Ok(Command::Quit));
+        assert!(Command::parse("add").is_err());
+        assert!(Command::parse("sort author").is_err());
+        assert!(Command::parse("").is_err());
+    }
+}
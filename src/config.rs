@@ -0,0 +1,315 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use prss::{CacheCompression, FetchOptions};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::template::EntryFormat;
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedOrder {
+    Recent,
+    Listed,
+    Alphabetical,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Date,
+    Feed,
+    Title,
+}
+
+impl SortKey {
+    /// Newest first for dates, A to Z otherwise.
+    pub fn default_direction(self) -> SortDirection {
+        match self {
+            SortKey::Date => SortDirection::Descending,
+            SortKey::Feed | SortKey::Title => SortDirection::Ascending,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    pub fn flipped(self) -> SortDirection {
+        match self {
+            SortDirection::Ascending => SortDirection::Descending,
+            SortDirection::Descending => SortDirection::Ascending,
+        }
+    }
+}
+
+/// How entries were last sorted while browsing, which is kept between
+/// sessions.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SortOrder {
+    pub sort: SortKey,
+    pub sort_direction: SortDirection,
+}
+
+/// Whether read entries are shown when the interface starts.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadEntries {
+    Hide,
+    Show,
+    /// Shown below all unread entries.
+    Bottom,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub wrap_around: bool,
+    #[serde(deserialize_with = "deserialize_regexes")]
+    pub mute: Vec<Regex>,
+    #[serde(deserialize_with = "deserialize_keywords")]
+    pub highlight: Vec<Regex>,
+    pub feed_order: FeedOrder,
+    pub read_entries: ReadEntries,
+    pub sort: SortKey,
+    /// The default direction of `sort` if unset.
+    pub sort_direction: Option<SortDirection>,
+    /// The commands to open entries with, tried in order.
+    #[serde(deserialize_with = "deserialize_commands")]
+    pub browser: Vec<String>,
+    pub terminal_browser: String,
+    pub digest_command: Option<String>,
+    /// How many entries `O` opens.
+    pub open_recent: usize,
+    /// Ask before opening more entries than this at once.
+    pub confirm_open_above: usize,
+    pub max_title_width: Option<usize>,
+    #[serde(deserialize_with = "deserialize_entry_format")]
+    pub entry_format: EntryFormat,
+    /// The header above the text of an entry viewed with `v`.
+    #[serde(deserialize_with = "deserialize_entry_format")]
+    pub pager_header: EntryFormat,
+    pub date_headers: bool,
+    /// How long `z` hides the selected entry for.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub snooze: Duration,
+    pub highlight_symbol: String,
+    pub min_width: u16,
+    pub min_height: u16,
+    /// The widest the interface gets; wider terminals leave empty margins on
+    /// both sides.
+    pub max_width: Option<u16>,
+    pub cache_compression: CacheCompression,
+    pub min_refresh_minutes: Option<u64>,
+    pub max_cache_age_hours: Option<u64>,
+    pub max_concurrent_fetches: usize,
+    pub next_pages: usize,
+    pub max_entries_per_feed: Option<usize>,
+    pub fetch_timeout_seconds: Option<u64>,
+    /// Change the urls of feeds that have moved for good in feeds.txt.
+    pub update_moved_feeds: bool,
+    /// Mark the selected entry read once it has been selected this long.
+    pub auto_mark_read_seconds: Option<u64>,
+    /// Fetch the feeds again in the background this often.
+    pub background_refresh_minutes: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            wrap_around: true,
+            mute: vec![],
+            highlight: vec![],
+            feed_order: FeedOrder::Recent,
+            read_entries: ReadEntries::Hide,
+            sort: SortKey::Date,
+            sort_direction: None,
+            browser: vec![
+                "$BROWSER".to_string(),
+                "xdg-open".to_string(),
+                "open".to_string(),
+            ],
+            terminal_browser: "w3m".to_string(),
+            digest_command: None,
+            open_recent: 5,
+            confirm_open_above: 10,
+            max_title_width: None,
+            entry_format: EntryFormat::default(),
+            pager_header: "{title}\nFeed: {feed}\nAuthor: {author}\nDate: {date}\nLink: {url}"
+                .parse()
+                .unwrap(),
+            date_headers: false,
+            snooze: Duration::from_secs(24 * 60 * 60),
+            highlight_symbol: "> ".to_string(),
+            min_width: 40,
+            min_height: 12,
+            max_width: None,
+            cache_compression: CacheCompression::None,
+            min_refresh_minutes: None,
+            max_cache_age_hours: None,
+            max_concurrent_fetches: 8,
+            next_pages: 0,
+            max_entries_per_feed: None,
+            fetch_timeout_seconds: None,
+            update_moved_feeds: false,
+            auto_mark_read_seconds: None,
+            background_refresh_minutes: None,
+        }
+    }
+}
+
+/// A TOML error along with the line it is on, such as
+///
+/// ```text
+/// prss.toml:3:8: invalid type: integer `5`, expected a string
+///   |
+/// 3 | snooze = 5
+///   |        ^
+/// ```
+fn describe_error(path: &str, content: &str, error: &toml::de::Error) -> String {
+    let location = Regex::new(r" at line \d+ column \d+$").unwrap();
+    let message = error.to_string();
+    let message = location.replace(&message, "");
+    // Unknown fields are reported at the start of the file, so the key is
+    // looked up instead.
+    let unknown = Regex::new(r"^unknown field `([^`]*)`").unwrap();
+    let position = match unknown.captures(&message) {
+        Some(field) => content.lines().enumerate().find_map(|(i, line)| {
+            let key = line.trim_start();
+            let rest = key.strip_prefix(&field[1])?;
+            Some((i, line.len() - key.len())).filter(|_| rest.trim_start().starts_with('='))
+        }),
+        None => error.line_col(),
+    };
+    match position {
+        Some((line, column)) => {
+            let text = content.lines().nth(line).unwrap_or("");
+            let number = (line + 1).to_string();
+            let margin = " ".repeat(number.len());
+            format!(
+                "{}:{}:{}: {}\n{} |\n{} | {}\n{} | {}^",
+                path,
+                line + 1,
+                column + 1,
+                message,
+                margin,
+                number,
+                text,
+                margin,
+                " ".repeat(column)
+            )
+        }
+        None => format!("{}: {}", path, message),
+    }
+}
+
+fn deserialize_regexes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(de::Error::custom))
+        .collect()
+}
+
+fn deserialize_keywords<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|keyword| {
+            match keyword.strip_prefix('/').and_then(|k| k.strip_suffix('/')) {
+                Some(pattern) => Regex::new(pattern),
+                None => Regex::new(&format!("(?i){}", regex::escape(keyword))),
+            }
+            .map_err(de::Error::custom)
+        })
+        .collect()
+}
+
+/// A single command, or a list of commands.
+fn deserialize_commands<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Commands {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match Commands::deserialize(deserializer)? {
+        Commands::One(command) => vec![command],
+        Commands::Many(commands) => commands,
+    })
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    crate::parse_duration(&String::deserialize(deserializer)?).map_err(de::Error::custom)
+}
+
+fn deserialize_entry_format<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<EntryFormat, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(de::Error::custom)
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(path).with_context(|| format!("{}", path.display()))?;
+        toml::from_str(&content).map_err(|e| {
+            anyhow!(
+                "{}",
+                describe_error(&path.display().to_string(), &content, &e)
+            )
+        })
+    }
+
+    pub fn fetch_options(&self) -> FetchOptions {
+        FetchOptions {
+            compression: self.cache_compression,
+            min_refresh: self
+                .min_refresh_minutes
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+            max_age: self
+                .max_cache_age_hours
+                .map(|hours| Duration::from_secs(hours * 60 * 60)),
+            next_pages: self.next_pages,
+            max_entries: self.max_entries_per_feed,
+            timeout: self.fetch_timeout_seconds.map(Duration::from_secs),
+            format: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(content: &str) -> String {
+        let e = toml::from_str::<Config>(content).err().unwrap();
+        describe_error("prss.toml", content, &e)
+    }
+
+    #[test]
+    fn errors_with_lines() {
+        assert_eq!(
+            error("wrap_around = true\nsnooze = 5\n"),
+            "prss.toml:2:10: invalid type: integer `5`, expected a string for key `snooze`\n  \
+             |\n2 | snooze = 5\n  |          ^"
+        );
+        let unknown = error("wrap_around = true\n  browsr = \"firefox\"\n");
+        assert!(unknown.starts_with("prss.toml:2:3: unknown field `browsr`, expected one of"));
+        assert!(unknown.ends_with("\n  |\n2 |   browsr = \"firefox\"\n  |   ^"));
+    }
+}
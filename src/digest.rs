@@ -0,0 +1,376 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use html_escape::{decode_html_entities, encode_double_quoted_attribute, encode_text};
+use regex::Regex;
+
+use crate::feedlist::FeedListEntry;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DigestFormat {
+    Html,
+    Text,
+}
+
+impl FromStr for DigestFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<DigestFormat, String> {
+        match format {
+            "html" => Ok(DigestFormat::Html),
+            "text" => Ok(DigestFormat::Text),
+            _ => Err(format!(
+                "Unknown digest format {}, expected html or text",
+                format
+            )),
+        }
+    }
+}
+
+impl DigestFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            DigestFormat::Html => "text/html; charset=utf-8",
+            DigestFormat::Text => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// The text of an HTML summary, without tags and entities.
+fn plain_text(html: &str) -> String {
+    let tags = Regex::new(r"<[^>]*>").unwrap();
+    let text = tags.replace_all(html, " ");
+    decode_html_entities(&text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The text of an HTML summary as paragraphs separated by blank lines, such as
+/// for reading it in a pager.
+pub fn paragraphs(html: &str) -> String {
+    let breaks =
+        Regex::new(r"(?i)<br\s*/?>|</?(p|div|h[1-6]|ul|ol|li|blockquote|pre|tr)\b[^>]*>").unwrap();
+    let items = Regex::new(r"(?i)<li\b[^>]*>").unwrap();
+    let html = items.replace_all(html, "\n\n- ");
+    let html = breaks.replace_all(&html, "\n\n");
+    html.split("\n\n")
+        .map(plain_text)
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The elements kept in the summaries of HTML digests, without their
+/// attributes, other than the targets of links and images.
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "cite",
+    "code",
+    "dd",
+    "del",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+
+/// The elements dropped from summaries along with their content.
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "noscript", "template", "svg", "math",
+];
+
+/// The HTML of a summary with only the elements in `ALLOWED_TAGS`, so that a
+/// feed can't run scripts or style the digest. Other tags are removed, keeping
+/// their text, and links only keep http, https and mailto targets.
+fn sanitize(html: &str) -> String {
+    let tags = Regex::new(r"(?s)<!--.*?(-->|$)|<(/?)([A-Za-z][A-Za-z0-9]*)([^>]*)>").unwrap();
+    let mut out = String::new();
+    let mut rest = html;
+    while let Some(tag) = tags.captures(rest) {
+        let whole = tag.get(0).unwrap();
+        out.push_str(&text(&rest[..whole.start()]));
+        rest = &rest[whole.end()..];
+        let name = match tag.get(3) {
+            Some(name) => name.as_str().to_lowercase(),
+            None => continue,
+        };
+        let closing = &tag[2] == "/";
+        if DROPPED_TAGS.contains(&name.as_str()) {
+            if !closing {
+                let end = Regex::new(&format!(r"(?i)</{}\s*>", name)).unwrap();
+                rest = end.find(rest).map_or("", |end| &rest[end.end()..]);
+            }
+            continue;
+        }
+        if !ALLOWED_TAGS.contains(&name.as_str()) {
+            continue;
+        }
+        if closing {
+            write!(out, "</{}>", name).unwrap();
+            continue;
+        }
+        write!(out, "<{}", name).unwrap();
+        let kept: &[&str] = match name.as_str() {
+            "a" => &["href"],
+            "img" => &["src", "alt"],
+            _ => &[],
+        };
+        for attribute in kept {
+            if let Some(value) = attribute_value(&tag[4], attribute) {
+                let url = *attribute != "alt";
+                if !url
+                    || ["http://", "https://", "mailto:"]
+                        .iter()
+                        .any(|scheme| value.to_lowercase().starts_with(scheme))
+                {
+                    write!(
+                        out,
+                        " {}=\"{}\"",
+                        attribute,
+                        encode_double_quoted_attribute(&value)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        out.push('>');
+    }
+    out.push_str(&text(rest));
+    out
+}
+
+/// Text between tags, escaped again, without escaping its entities twice.
+fn text(html: &str) -> String {
+    encode_text(&decode_html_entities(html)).into_owned()
+}
+
+/// The decoded value of `name` in the attributes of a tag.
+fn attribute_value(attributes: &str, name: &str) -> Option<String> {
+    let attribute = Regex::new(&format!(
+        r#"(?i)(?:^|\s){}\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#,
+        name
+    ))
+    .unwrap();
+    let value = attribute.captures(attributes)?;
+    let value = value
+        .get(1)
+        .or_else(|| value.get(2))
+        .or_else(|| value.get(3))?;
+    Some(decode_html_entities(value.as_str()).trim().to_string())
+}
+
+/// The entries grouped by feed, in the order the feeds first appear.
+fn by_feed<'a>(entries: &[&'a FeedListEntry]) -> Vec<(&'a str, Vec<&'a FeedListEntry>)> {
+    let mut feeds: Vec<(&str, Vec<&FeedListEntry>)> = vec![];
+    for entry in entries {
+        match feeds
+            .iter_mut()
+            .find(|(title, _)| *title == entry.feed_title)
+        {
+            Some((_, feed)) => feed.push(entry),
+            None => feeds.push((&entry.feed_title, vec![entry])),
+        }
+    }
+    feeds
+}
+
+/// Render the entries as a digest, such as for a daily email.
+pub fn render<'a>(
+    format: DigestFormat,
+    title: &str,
+    entries: impl IntoIterator<Item = &'a FeedListEntry>,
+) -> String {
+    let entries = entries.into_iter().collect::<Vec<_>>();
+    let mut out = String::new();
+    match format {
+        DigestFormat::Text => {
+            writeln!(out, "{}\n", title).unwrap();
+            if entries.is_empty() {
+                writeln!(out, "No new entries.").unwrap();
+            }
+            for (feed, entries) in by_feed(&entries) {
+                writeln!(out, "{}\n{}\n", feed, "=".repeat(feed.chars().count())).unwrap();
+                for entry in entries {
+                    writeln!(out, "{}", entry.title).unwrap();
+                    writeln!(out, "{}", entry.url).unwrap();
+                    writeln!(out, "{}", entry.date.format("%Y-%m-%d %H:%M")).unwrap();
+                    if let Some(summary) = entry.summary.as_deref().map(plain_text) {
+                        writeln!(out, "\n{}", summary).unwrap();
+                    }
+                    writeln!(out).unwrap();
+                }
+            }
+        }
+        DigestFormat::Html => {
+            writeln!(
+                out,
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                 <title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>",
+                encode_text(title)
+            )
+            .unwrap();
+            if entries.is_empty() {
+                writeln!(out, "<p>No new entries.</p>").unwrap();
+            }
+            for (feed, entries) in by_feed(&entries) {
+                writeln!(out, "<h2>{}</h2>", encode_text(feed)).unwrap();
+                for entry in entries {
+                    writeln!(
+                        out,
+                        "<h3><a href=\"{}\">{}</a></h3>\n<p><small>{}</small></p>",
+                        encode_double_quoted_attribute(&entry.url),
+                        encode_text(&entry.title),
+                        entry.date.format("%Y-%m-%d %H:%M")
+                    )
+                    .unwrap();
+                    if let Some(summary) = &entry.summary {
+                        writeln!(out, "<div>{}</div>", sanitize(summary)).unwrap();
+                    }
+                }
+            }
+            writeln!(out, "</body>\n</html>").unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn entry(feed_title: &str, title: &str, summary: Option<&str>) -> FeedListEntry {
+        let url = format!("https://example.com/{}", title.replace(' ', "-"));
+        FeedListEntry {
+            title: title.to_string(),
+            feed_title: feed_title.to_string(),
+            feed_url: "https://example.com/feed.xml".to_string(),
+            hub: None,
+            homepage: None,
+            links: vec![url.clone()],
+            url,
+            date: Utc.ymd(2021, 2, 3).and_hms(10, 0, 0),
+            author: None,
+            summary: summary.map(String::from),
+            image_url: None,
+            comments: None,
+            comments_url: None,
+            categories: vec![],
+        }
+    }
+
+    #[test]
+    fn text() {
+        let entries = [
+            entry(
+                "Blog",
+                "Hello world",
+                Some("<p>Hi &amp; <b>welcome</b></p>"),
+            ),
+            entry("News", "Headline", None),
+            entry("Blog", "Second post", None),
+        ];
+        assert_eq!(
+            render(DigestFormat::Text, "prss digest", &entries),
+            "prss digest\n\n\
+             Blog\n====\n\n\
+             Hello world\nhttps://example.com/Hello-world\n2021-02-03 10:00\n\nHi & welcome\n\n\
+             Second post\nhttps://example.com/Second-post\n2021-02-03 10:00\n\n\
+             News\n====\n\n\
+             Headline\nhttps://example.com/Headline\n2021-02-03 10:00\n\n"
+        );
+    }
+
+    #[test]
+    fn summary_paragraphs() {
+        assert_eq!(
+            paragraphs(
+                "<p>Hi &amp;\n<b>welcome</b></p><p>Second<br/>line</p>\
+                 <ul><li>One</li><li>Two</li></ul>"
+            ),
+            "Hi & welcome\n\nSecond\n\nline\n\n- One\n\n- Two"
+        );
+    }
+
+    #[test]
+    fn html_escaped() {
+        let html = render(
+            DigestFormat::Html,
+            "prss digest",
+            &[entry("Q&A", "<script> tags", Some("<p>Kept</p>"))],
+        );
+        assert!(html.contains("<h2>Q&amp;A</h2>"));
+        assert!(html.contains("\">&lt;script&gt; tags</a>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<div><p>Kept</p></div>"));
+    }
+
+    #[test]
+    fn escaped_summaries() {
+        let summary = "<p>Vec&lt;T&gt; &amp;lt;b&amp;gt;</p>";
+        assert_eq!(paragraphs(summary), "Vec<T> &lt;b&gt;");
+        let entries = [entry("Blog", "Generics", Some(summary))];
+        assert!(
+            render(DigestFormat::Text, "prss digest", &entries).contains("\nVec<T> &lt;b&gt;\n")
+        );
+        assert!(render(DigestFormat::Html, "prss digest", &entries)
+            .contains("<div><p>Vec&lt;T&gt; &amp;lt;b&amp;gt;</p></div>"));
+    }
+
+    #[test]
+    fn summaries_sanitized() {
+        assert_eq!(
+            sanitize(
+                "<p class=\"x\" onclick=\"steal()\">Hi <a href=\"https://example.com/?a=1&amp;b\" \
+                 target=_blank>there</a><script>alert(1)</script></p>"
+            ),
+            "<p>Hi <a href=\"https://example.com/?a=1&amp;b\">there</a></p>"
+        );
+        assert_eq!(
+            sanitize("<a href=\"javascript:alert(1)\">x</a><img src=x onerror=alert(1)>"),
+            "<a>x</a><img>"
+        );
+        assert_eq!(
+            sanitize("<STYLE>body{}</style><blink>1 < 2 &amp; 3</blink><!-- note -->"),
+            "1 &lt; 2 &amp; 3"
+        );
+        assert_eq!(sanitize("<script>never closed"), "");
+    }
+}
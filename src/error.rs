@@ -0,0 +1,125 @@
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::FeedFormat;
+
+/// The ways fetching, caching and parsing feeds can fail.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The request for a feed failed.
+    #[error(transparent)]
+    Network(#[from] reqwest::Error),
+    /// The server answered with an error status.
+    #[error("Feed {url} returned HTTP {status}")]
+    Status {
+        url: String,
+        status: reqwest::StatusCode,
+    },
+    /// The document is not valid in the given format.
+    #[error("{source}")]
+    Parse {
+        format: FeedFormat,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The document can't be read as either Atom or RSS. `served_as` is the
+    /// format the server claimed, with the reason it couldn't be read as such.
+    #[error("Couldn't read Atom or RSS from url: {url}{}", served_as_note(.served_as))]
+    UnsupportedFormat {
+        url: String,
+        served_as: Option<Box<Error>>,
+    },
+    /// Reading or writing a file in the cache failed.
+    #[error("{}: {source}", .path.display())]
+    Cache { path: PathBuf, source: io::Error },
+    /// Reading a local feed failed.
+    #[error("{}: {source}", .path.display())]
+    LocalFeed { path: PathBuf, source: io::Error },
+    #[error(
+        "could not determine the home directory; set HOME, or set XDG_CONFIG_HOME, \
+         XDG_DATA_HOME and XDG_CACHE_HOME"
+    )]
+    Directories(#[from] xdg::BaseDirectoriesError),
+    #[error("Feed {url} redirected more than {} times", crate::MAX_REDIRECTS)]
+    TooManyRedirects { url: String },
+    /// The feed at `url`, fetched over https, redirected to plain http.
+    #[error("Feed {url} redirected from https to {to}")]
+    InsecureRedirect { url: String, to: String },
+    /// The fetch took longer than `FetchOptions::timeout`.
+    #[error("timed out after {}", seconds(.0))]
+    TimedOut(Duration),
+    /// Fetching the feed at `url` failed after `elapsed`.
+    #[error("{source}")]
+    Fetch {
+        url: String,
+        elapsed: Duration,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// The HTTP status the server answered with, if it answered.
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        match self {
+            Error::Status { status, .. } => Some(*status),
+            Error::Network(e) => e.status(),
+            Error::Fetch { source, .. } => source.status(),
+            _ => None,
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            Error::TimedOut(_) => true,
+            Error::Network(e) => e.is_timeout(),
+            Error::Fetch { source, .. } => source.is_timeout(),
+            _ => false,
+        }
+    }
+
+    /// How long the failed fetch took.
+    pub fn elapsed(&self) -> Option<Duration> {
+        match self {
+            Error::Fetch { elapsed, .. } => Some(*elapsed),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn served_as_note(served_as: &Option<Box<Error>>) -> String {
+    match served_as.as_deref() {
+        Some(e @ Error::Parse { format, .. }) => format!(" (served as {}: {})", format, e),
+        _ => String::new(),
+    }
+}
+
+/// `duration` in whole milliseconds below a second, and in tenths of seconds
+/// otherwise.
+fn seconds(duration: &Duration) -> String {
+    if *duration < Duration::from_secs(1) {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+/// Wrap an error from a file in the cache at `path`.
+pub(crate) fn cache_error(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Error {
+    let path = path.into();
+    move |source| Error::Cache { path, source }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timed_out() {
+        let error = Error::TimedOut(Duration::from_millis(250));
+        assert_eq!(error.to_string(), "timed out after 250ms");
+        let error = Error::TimedOut(Duration::from_millis(1500));
+        assert_eq!(error.to_string(), "timed out after 1.5s");
+    }
+}
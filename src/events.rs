@@ -0,0 +1,108 @@
+use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use termion::event::Key;
+use termion::input::TermRead;
+use tokio::signal::unix::{signal, SignalKind};
+
+pub enum Event {
+    Key(io::Result<Key>),
+    Resize,
+    /// No key was pressed in the time given to `next_event_timeout`.
+    Tick,
+}
+
+/// Keys read from stdin, interleaved with the resizes of the terminal and,
+/// when waiting with a timeout, ticks.
+///
+/// A key is only read from stdin when one is asked for, so that no key is
+/// taken away from programs run in the terminal in the meantime.
+pub struct Events {
+    requests: Sender<()>,
+    events: Receiver<Option<Event>>,
+    pending: bool,
+    closed: bool,
+}
+
+impl Events {
+    pub fn new() -> Events {
+        let (requests, pending_requests) = mpsc::channel::<()>();
+        let (sender, events) = mpsc::channel();
+
+        let keys = sender.clone();
+        thread::spawn(move || {
+            let mut stdin = io::stdin().keys();
+            for () in pending_requests {
+                let key = stdin.next();
+                let done = key.is_none();
+                if keys.send(key.map(Event::Key)).is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        if let Ok(mut resizes) = signal(SignalKind::window_change()) {
+            tokio::spawn(async move {
+                while resizes.recv().await.is_some() {
+                    if sender.send(Some(Event::Resize)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Events {
+            requests,
+            events,
+            pending: false,
+            closed: false,
+        }
+    }
+
+    /// Wait for the next key or resize. `None` means stdin has been closed.
+    pub fn next_event(&mut self) -> Option<Event> {
+        if self.closed {
+            return None;
+        }
+        if !self.pending {
+            self.requests.send(()).ok()?;
+            self.pending = true;
+        }
+        let event = self.events.recv().ok().flatten();
+        self.received(event)
+    }
+
+    /// Like `next_event`, but gives up with `Event::Tick` after `timeout`. The
+    /// key asked for is then read by the next call.
+    pub fn next_event_timeout(&mut self, timeout: Duration) -> Option<Event> {
+        if self.closed {
+            return None;
+        }
+        if !self.pending {
+            self.requests.send(()).ok()?;
+            self.pending = true;
+        }
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => self.received(event),
+            Err(RecvTimeoutError::Timeout) => Some(Event::Tick),
+            Err(RecvTimeoutError::Disconnected) => self.received(None),
+        }
+    }
+
+    fn received(&mut self, event: Option<Event>) -> Option<Event> {
+        match event {
+            Some(Event::Resize) => Some(Event::Resize),
+            Some(Event::Tick) => Some(Event::Tick),
+            Some(key) => {
+                self.pending = false;
+                Some(key)
+            }
+            None => {
+                self.closed = true;
+                None
+            }
+        }
+    }
+}
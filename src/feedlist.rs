@@ -0,0 +1,1458 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use caseless::Caseless;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use prss::Feed;
+use regex::Regex;
+use tui::widgets::ListState;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::{Config, FeedOrder, ReadEntries, SortDirection, SortKey, SortOrder};
+
+#[derive(Clone)]
+pub struct FeedListEntry {
+    pub title: String,
+    pub feed_title: String,
+    pub feed_url: String,
+    pub hub: Option<String>,
+    pub homepage: Option<String>,
+    pub url: String,
+    pub links: Vec<String>,
+    pub date: DateTime<Utc>,
+    pub author: Option<String>,
+    pub summary: Option<String>,
+    pub image_url: Option<String>,
+    pub comments: Option<u64>,
+    pub comments_url: Option<String>,
+    pub categories: Vec<String>,
+}
+
+fn list_entries(feed: &Feed) -> Vec<FeedListEntry> {
+    feed.entries
+        .iter()
+        .map(|e| FeedListEntry {
+            title: e.title.clone(),
+            feed_title: feed.title.clone(),
+            feed_url: feed.url.clone(),
+            hub: feed.hub.clone(),
+            homepage: feed.homepage.clone(),
+            url: e.url.clone(),
+            links: e.links.clone(),
+            date: e.date,
+            author: e.author.clone(),
+            summary: e.summary.clone(),
+            image_url: e.image_url.clone(),
+            comments: e.comments,
+            comments_url: e.comments_url.clone(),
+            categories: e.categories.clone(),
+        })
+        .collect()
+}
+
+fn is_muted(mute: &[Regex], entry: &FeedListEntry) -> bool {
+    let qualified = format!("{}: {}", entry.feed_title, entry.title);
+    mute.iter()
+        .any(|re| re.is_match(&entry.title) || re.is_match(&qualified))
+}
+
+/// `text` in a form where text that only differs in case compares equal. This
+/// is Unicode case folding, so that `ß` matches `SS`, and the text is
+/// normalized, so that composed and decomposed accents match, but an accented
+/// letter doesn't match the letter without the accent.
+///
+/// Folding turns the Turkish `İ` into an `i` with a combining dot above it,
+/// which is dropped, as an `i` is dotted anyway, so that `İzmir` matches
+/// `izmir` as well as `İZMİR`.
+fn fold(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars().nfd().default_case_fold().nfd() {
+        if c == '\u{307}' && folded.ends_with(['i', 'j']) {
+            continue;
+        }
+        folded.push(c);
+    }
+    folded.nfc().collect()
+}
+
+/// The heading of the entries published around `date`, relative to `today`.
+fn date_group(date: DateTime<Utc>, today: NaiveDate) -> String {
+    let day = date.with_timezone(&Local).naive_local().date();
+    match (today - day).num_days() {
+        days if days <= 0 => "Today".to_string(),
+        1 => "Yesterday".to_string(),
+        2..=7 => "Last week".to_string(),
+        _ => day.format("%Y-%m-%d").to_string(),
+    }
+}
+
+pub struct FeedInfo {
+    pub url: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Flat,
+    Grouped,
+    Summary,
+}
+
+#[derive(Clone, Copy)]
+pub enum Row {
+    Feed(usize),
+    Entry(usize),
+    /// The date heading above the entry, which can't be selected.
+    Date(usize),
+}
+
+/// How many actions `FeedList::undo` can go back.
+const UNDO_LIMIT: usize = 20;
+
+/// What an action changed, to be put back by `FeedList::undo`.
+struct Snapshot {
+    action: &'static str,
+    read: HashSet<String>,
+    snoozed: HashMap<String, DateTime<Utc>>,
+    hidden: HashSet<String>,
+    selected: Option<String>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum RowKey {
+    Feed(String),
+    Entry(String),
+}
+
+pub struct FeedList {
+    pub feeds: Vec<FeedInfo>,
+    entries: Vec<FeedListEntry>,
+    pub items: Vec<FeedListEntry>,
+    pub rows: Vec<Row>,
+    pub read: HashSet<String>,
+    /// When to remind of entries, by url.
+    pub reminders: HashMap<String, DateTime<Utc>>,
+    /// Until when entries are hidden, by url.
+    pub snoozed: HashMap<String, DateTime<Utc>>,
+    /// The entries that are never shown, unlike read entries, which can be.
+    pub hidden: HashSet<String>,
+    show_read: bool,
+    unread_first: bool,
+    sort: SortKey,
+    sort_direction: SortDirection,
+    author: Option<Option<String>>,
+    search: Option<String>,
+    mute: Vec<Regex>,
+    pub muting: bool,
+    highlight: Vec<Regex>,
+    wrap_around: bool,
+    feed_order: FeedOrder,
+    date_headers: bool,
+    today: NaiveDate,
+    pub view: View,
+    /// Whether feeds have been moved since the list was created.
+    pub reordered: bool,
+    expanded: HashSet<String>,
+    focus: Option<String>,
+    positions: HashMap<String, String>,
+    undo: Vec<Snapshot>,
+    pub state: ListState,
+}
+
+fn feed_infos(feeds: &[Feed]) -> Vec<FeedInfo> {
+    feeds
+        .iter()
+        .map(|feed| FeedInfo {
+            url: feed.url.clone(),
+            title: feed.title.clone(),
+            description: feed.description.clone(),
+            warnings: feed.warnings.clone(),
+        })
+        .collect()
+}
+
+fn sorted_entries(feeds: &[Feed]) -> Vec<FeedListEntry> {
+    let mut items = feeds
+        .iter()
+        .map(list_entries)
+        .collect::<Vec<Vec<_>>>()
+        .concat();
+
+    // Newest first, falling back to feed and entry title so that entries
+    // published at the same time keep a stable order.
+    items.sort_by(|a, b| {
+        b.date
+            .cmp(&a.date)
+            .then_with(|| a.feed_title.cmp(&b.feed_title))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    items
+}
+
+impl FeedList {
+    pub fn new(feeds: Vec<Feed>, read: HashSet<String>, config: &Config) -> FeedList {
+        let mut feedlist = FeedList {
+            feeds: feed_infos(&feeds),
+            entries: sorted_entries(&feeds),
+            items: vec![],
+            rows: vec![],
+            read,
+            reminders: HashMap::new(),
+            snoozed: HashMap::new(),
+            hidden: HashSet::new(),
+            show_read: config.read_entries != ReadEntries::Hide,
+            unread_first: config.read_entries == ReadEntries::Bottom,
+            sort: config.sort,
+            sort_direction: config
+                .sort_direction
+                .unwrap_or_else(|| config.sort.default_direction()),
+            author: None,
+            search: None,
+            mute: config.mute.clone(),
+            muting: true,
+            highlight: config.highlight.clone(),
+            wrap_around: config.wrap_around,
+            feed_order: config.feed_order,
+            date_headers: config.date_headers,
+            today: Local::today().naive_local(),
+            view: View::Flat,
+            reordered: false,
+            expanded: HashSet::new(),
+            focus: None,
+            positions: HashMap::new(),
+            undo: vec![],
+            state: ListState::default(),
+        };
+        feedlist.apply_filter();
+        feedlist
+    }
+
+    /// Apply the settings of a reloaded config, keeping the view and the
+    /// selection.
+    pub fn set_config(&mut self, config: &Config) {
+        self.show_read = config.read_entries != ReadEntries::Hide;
+        self.unread_first = config.read_entries == ReadEntries::Bottom;
+        self.sort = config.sort;
+        self.sort_direction = config
+            .sort_direction
+            .unwrap_or_else(|| config.sort.default_direction());
+        self.mute = config.mute.clone();
+        self.highlight = config.highlight.clone();
+        self.wrap_around = config.wrap_around;
+        self.feed_order = config.feed_order;
+        self.date_headers = config.date_headers;
+        self.apply_filter();
+    }
+
+    /// Replace the feeds, such as after fetching them again, keeping the view
+    /// and the selection. Returns how many of the entries are new.
+    pub fn set_feeds(&mut self, feeds: Vec<Feed>) -> usize {
+        let known = self
+            .entries
+            .iter()
+            .map(|e| e.url.clone())
+            .collect::<HashSet<_>>();
+        let order = self.feed_urls();
+        // The rows refer to the old feeds and entries by index.
+        let following = self.following();
+        self.feeds = feed_infos(&feeds);
+        if self.reordered {
+            self.feeds.sort_by_key(|f| {
+                order
+                    .iter()
+                    .position(|url| url == &f.url)
+                    .unwrap_or(order.len())
+            });
+        }
+        self.entries = sorted_entries(&feeds);
+        self.filter_selecting(following);
+        self.entries
+            .iter()
+            .filter(|e| !known.contains(&e.url))
+            .count()
+    }
+
+    fn row_key(&self, row: Row) -> Option<RowKey> {
+        match row {
+            Row::Feed(i) => Some(RowKey::Feed(self.feeds[i].url.clone())),
+            Row::Entry(i) => Some(RowKey::Entry(self.items[i].url.clone())),
+            Row::Date(_) => None,
+        }
+    }
+
+    /// The selected row and the rows below it, to select the first of them
+    /// that is left after filtering.
+    fn following(&self) -> Vec<RowKey> {
+        match self.state.selected() {
+            Some(i) => self.rows[i..]
+                .iter()
+                .filter_map(|row| self.row_key(*row))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn apply_filter(&mut self) {
+        let following = self.following();
+        self.filter_selecting(following);
+    }
+
+    fn filter_selecting(&mut self, following: Vec<RowKey>) {
+        self.today = Local::today().naive_local();
+        let now = Utc::now();
+        let reminders = &self.reminders;
+        let due = |e: &FeedListEntry| reminders.get(&e.url).is_some_and(|date| *date <= now);
+        let snoozed = &self.snoozed;
+        let hidden = &self.hidden;
+        let author = &self.author;
+        let read = &self.read;
+        let show_read = self.show_read;
+        let mute: &[Regex] = if self.muting { &self.mute } else { &[] };
+        let focus = &self.focus;
+        let search = self.search.as_deref().map(fold);
+        self.items = self
+            .entries
+            .iter()
+            .filter(|e| focus.as_ref().is_none_or(|url| &e.feed_url == url))
+            .filter(|e| author.as_ref().is_none_or(|a| &e.author == a))
+            .filter(|e| show_read || !read.contains(&e.url) || due(e))
+            .filter(|e| !is_muted(mute, e))
+            .filter(|e| snoozed.get(&e.url).is_none_or(|until| *until <= now))
+            .filter(|e| !hidden.contains(&e.url))
+            .filter(|e| search.as_ref().is_none_or(|q| fold(&e.title).contains(q)))
+            .cloned()
+            .collect();
+        // The entries are newest first at this point.
+        let descending = self.sort_direction == SortDirection::Descending;
+        let direction = |ordering: Ordering| {
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        match self.sort {
+            SortKey::Date if !descending => self.items.reverse(),
+            SortKey::Date => {}
+            SortKey::Feed => self
+                .items
+                .sort_by(|a, b| direction(a.feed_title.cmp(&b.feed_title))),
+            SortKey::Title => self
+                .items
+                .sort_by(|a, b| direction(a.title.to_lowercase().cmp(&b.title.to_lowercase()))),
+        }
+        if self.unread_first {
+            self.items.sort_by_key(|e| read.contains(&e.url));
+        }
+        self.items.sort_by_key(|e| !due(e));
+
+        self.rows = self.build_rows();
+
+        let positions = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter_map(|(i, row)| Some((self.row_key(*row)?, i)))
+            .collect::<HashMap<_, _>>();
+        let selected = following.iter().find_map(|key| positions.get(key).copied());
+
+        self.select(selected.or_else(|| self.first_selectable()));
+    }
+
+    fn first_selectable(&self) -> Option<usize> {
+        self.rows
+            .iter()
+            .position(|row| !matches!(row, Row::Date(_)))
+    }
+
+    fn select(&mut self, i: Option<usize>) {
+        self.state.select(i);
+        if self.view == View::Grouped {
+            if let Some(entry) = self.get() {
+                let (feed_url, url) = (entry.feed_url.clone(), entry.url.clone());
+                self.positions.insert(feed_url, url);
+            }
+        }
+    }
+
+    fn feed_order(&self) -> Vec<usize> {
+        let mut order = (0..self.feeds.len()).collect::<Vec<_>>();
+        if self.feed_order == FeedOrder::Recent {
+            order.sort_by_key(|&i| {
+                self.items
+                    .iter()
+                    .position(|e| e.feed_url == self.feeds[i].url)
+                    .unwrap_or(self.items.len())
+            });
+        }
+        order
+    }
+
+    fn build_rows(&self) -> Vec<Row> {
+        match self.view {
+            View::Flat if self.date_headers && self.sort == SortKey::Date && !self.unread_first => {
+                let mut rows = vec![];
+                let mut group = None;
+                for (i, entry) in self.items.iter().enumerate() {
+                    let heading = date_group(entry.date, self.today);
+                    if group.as_ref() != Some(&heading) {
+                        rows.push(Row::Date(i));
+                        group = Some(heading);
+                    }
+                    rows.push(Row::Entry(i));
+                }
+                rows
+            }
+            View::Flat => (0..self.items.len()).map(Row::Entry).collect(),
+            View::Summary if self.focus.is_some() => {
+                (0..self.items.len()).map(Row::Entry).collect()
+            }
+            View::Summary => self.feed_order().into_iter().map(Row::Feed).collect(),
+            View::Grouped => {
+                let mut rows = vec![];
+                for i in self.feed_order() {
+                    rows.push(Row::Feed(i));
+                    if self.expanded.contains(&self.feeds[i].url) {
+                        rows.extend(
+                            self.items
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, e)| e.feed_url == self.feeds[i].url)
+                                .map(|(j, _)| Row::Entry(j)),
+                        );
+                    }
+                }
+                rows
+            }
+        }
+    }
+
+    /// Group the entries by date again once the day is over, as the headings
+    /// are relative to today.
+    pub fn update_today(&mut self) {
+        if self.today != Local::today().naive_local() {
+            self.apply_filter();
+        }
+    }
+
+    /// The heading of a `Row::Date` above the entry `i`.
+    pub fn date_heading(&self, i: usize) -> String {
+        date_group(self.items[i].date, self.today)
+    }
+
+    pub fn is_read(&self, entry: &FeedListEntry) -> bool {
+        self.read.contains(&entry.url)
+    }
+
+    /// Whether the reminder of `entry` has passed.
+    pub fn is_due(&self, entry: &FeedListEntry) -> bool {
+        self.reminders
+            .get(&entry.url)
+            .is_some_and(|date| *date <= Utc::now())
+    }
+
+    /// Replace the reminders and select the first entry, which is the first
+    /// entry whose reminder has passed, if any.
+    pub fn set_reminders(&mut self, reminders: HashMap<String, DateTime<Utc>>) {
+        self.reminders = reminders;
+        self.state.select(None);
+        self.apply_filter();
+    }
+
+    pub fn set_snoozed(&mut self, snoozed: HashMap<String, DateTime<Utc>>) {
+        self.snoozed = snoozed;
+        self.apply_filter();
+    }
+
+    /// Hide the selected entry until `until`, when it comes back unread.
+    pub fn snooze_selected(&mut self, until: DateTime<Utc>) {
+        if let Some(url) = self.get().map(|e| e.url.clone()) {
+            self.checkpoint("snoozing");
+            self.read.remove(&url);
+            self.snoozed.insert(url, until);
+            self.apply_filter();
+        }
+    }
+
+    pub fn set_hidden(&mut self, hidden: HashSet<String>) {
+        self.hidden = hidden;
+        self.apply_filter();
+    }
+
+    /// Never show the selected entry again, such as a pinned post that the
+    /// feed keeps listing.
+    pub fn hide_selected(&mut self) {
+        if let Some(url) = self.get().map(|e| e.url.clone()) {
+            self.checkpoint("hiding");
+            self.hidden.insert(url);
+            self.apply_filter();
+        }
+    }
+
+    /// Remind of the selected entry at `date`, or clear its reminder.
+    pub fn set_reminder(&mut self, date: Option<DateTime<Utc>>) {
+        if let Some(url) = self.get().map(|e| e.url.clone()) {
+            match date {
+                Some(date) => self.reminders.insert(url, date),
+                None => self.reminders.remove(&url),
+            };
+            self.apply_filter();
+        }
+    }
+
+    pub fn is_highlighted(&self, entry: &FeedListEntry) -> bool {
+        self.highlight.iter().any(|re| re.is_match(&entry.title))
+    }
+
+    /// The number of entries of all feeds, whether or not they are shown.
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn unread_count(&self, feed: &FeedInfo) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.feed_url == feed.url && !self.read.contains(&e.url))
+            .count()
+    }
+
+    pub fn newest_date(&self, feed: &FeedInfo) -> Option<DateTime<Utc>> {
+        self.entries
+            .iter()
+            .find(|e| e.feed_url == feed.url)
+            .map(|e| e.date)
+    }
+
+    pub fn is_expanded(&self, feed: &FeedInfo) -> bool {
+        self.expanded.contains(&feed.url)
+    }
+
+    pub fn toggle_show_read(&mut self) {
+        self.set_show_read(!self.show_read);
+    }
+
+    pub fn set_show_read(&mut self, show_read: bool) {
+        self.show_read = show_read;
+        self.apply_filter();
+    }
+
+    pub fn toggle_unread_first(&mut self) {
+        self.set_unread_first(!self.unread_first);
+    }
+
+    pub fn set_unread_first(&mut self, unread_first: bool) {
+        self.unread_first = unread_first;
+        self.apply_filter();
+    }
+
+    /// Sort entries by `sort`, in its default direction, showing unread
+    /// entries first only if `unread_first`.
+    pub fn set_sort(&mut self, sort: SortKey, unread_first: bool) {
+        self.sort = sort;
+        self.sort_direction = sort.default_direction();
+        self.set_unread_first(unread_first);
+    }
+
+    pub fn flip_sort_direction(&mut self) {
+        self.sort_direction = self.sort_direction.flipped();
+        self.apply_filter();
+    }
+
+    pub fn sort_order(&self) -> SortOrder {
+        SortOrder {
+            sort: self.sort,
+            sort_direction: self.sort_direction,
+        }
+    }
+
+    /// Sort entries as they were sorted in an earlier session.
+    pub fn set_sort_order(&mut self, order: SortOrder) {
+        self.sort = order.sort;
+        self.sort_direction = order.sort_direction;
+        self.apply_filter();
+    }
+
+    pub fn toggle_muting(&mut self) {
+        self.muting = !self.muting;
+        self.apply_filter();
+    }
+
+    /// Switch to `view`, or back to the flat view if it is already shown.
+    pub fn toggle_view(&mut self, view: View) {
+        self.view = if self.view == view { View::Flat } else { view };
+        self.focus = None;
+        self.apply_filter();
+    }
+
+    /// Show only the entries of the selected feed in the summary view.
+    pub fn drill_in(&mut self) -> bool {
+        match (self.view, &self.focus, self.selected_row()) {
+            (View::Summary, None, Some(Row::Feed(i))) => {
+                self.focus = Some(self.feeds[i].url.clone());
+                self.apply_filter();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Go back from the entries of a feed to the summary of all feeds.
+    pub fn drill_out(&mut self) -> bool {
+        let url = match self.focus.take() {
+            Some(url) => url,
+            None => return false,
+        };
+        self.apply_filter();
+        let feeds = &self.feeds;
+        let row = self
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::Feed(i) if feeds[*i].url == url));
+        self.select(row.or(self.state.selected()));
+        true
+    }
+
+    pub fn toggle_expanded(&mut self) -> bool {
+        match self.selected_row() {
+            Some(Row::Feed(i)) if self.view == View::Grouped => {
+                let url = self.feeds[i].url.clone();
+                if !self.expanded.remove(&url) {
+                    self.expanded.insert(url);
+                }
+                self.apply_filter();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move the selected feed up or down, when grouped by feed.
+    pub fn move_feed(&mut self, up: bool) {
+        if self.view != View::Grouped {
+            return;
+        }
+        let url = match self.selected_feed() {
+            Some(url) => url.to_string(),
+            None => return,
+        };
+        if self.feed_order == FeedOrder::Recent {
+            let order = self.feed_order();
+            let mut feeds = std::mem::take(&mut self.feeds)
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>();
+            self.feeds = order.into_iter().filter_map(|i| feeds[i].take()).collect();
+        }
+        self.feed_order = FeedOrder::Listed;
+
+        let i = self.feeds.iter().position(|f| f.url == url).unwrap();
+        let j = if up {
+            i.checked_sub(1)
+        } else {
+            Some(i + 1).filter(|&j| j < self.feeds.len())
+        };
+        if let Some(j) = j {
+            self.feeds.swap(i, j);
+            self.reordered = true;
+        }
+        // The rows refer to feeds by index, so they can't be used to find the
+        // selection again.
+        self.rows = self.build_rows();
+        let feeds = &self.feeds;
+        let header = self
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::Feed(i) if feeds[*i].url == url));
+        self.select(header);
+    }
+
+    pub fn feed_urls(&self) -> Vec<String> {
+        self.feeds.iter().map(|f| f.url.clone()).collect()
+    }
+
+    /// The `n` newest unread entries in the list.
+    pub fn recent_unread(&self, n: usize) -> Vec<FeedListEntry> {
+        let mut unread = self
+            .items
+            .iter()
+            .filter(|e| !self.is_read(e))
+            .cloned()
+            .collect::<Vec<_>>();
+        unread.sort_by_key(|e| std::cmp::Reverse(e.date));
+        unread.truncate(n);
+        unread
+    }
+
+    /// Mark the entry at `url` read, but keep it in the list until the list
+    /// is next filtered, so that it doesn't disappear while it is being read.
+    pub fn mark_read_in_place(&mut self, url: String) {
+        if !self.read.contains(&url) {
+            self.checkpoint("marking read");
+            self.read.insert(url);
+        }
+    }
+
+    pub fn mark_read(&mut self, urls: impl IntoIterator<Item = String>) {
+        let urls = urls.into_iter().collect::<Vec<_>>();
+        if !urls.is_empty() {
+            self.checkpoint("marking read");
+            self.read.extend(urls);
+            self.apply_filter();
+        }
+    }
+
+    pub fn mark_read_up_to_selected(&mut self) {
+        if let Some(i) = self.state.selected() {
+            let urls = self.rows[..=i]
+                .iter()
+                .filter_map(|row| match row {
+                    Row::Entry(j) => Some(self.items[*j].url.clone()),
+                    Row::Feed(_) | Row::Date(_) => None,
+                })
+                .collect::<Vec<_>>();
+            self.mark_read(urls);
+        }
+    }
+
+    /// Mark the selected entry read, then select the next unread entry.
+    pub fn mark_selected_read_and_advance(&mut self) {
+        if let Some(url) = self.get().map(|e| e.url.clone()) {
+            self.mark_read(vec![url]);
+            if self.get().is_some_and(|e| self.is_read(e)) {
+                self.next_unread();
+            }
+        }
+    }
+
+    /// Mark all entries of the selected feed read, when grouped by feed.
+    pub fn mark_feed_read(&mut self) {
+        if self.view == View::Flat {
+            return;
+        }
+        if let Some(url) = self.selected_feed().map(String::from) {
+            let urls = self
+                .entries
+                .iter()
+                .filter(|e| e.feed_url == url)
+                .map(|e| e.url.clone())
+                .collect::<Vec<_>>();
+            self.mark_read(urls);
+        }
+    }
+
+    /// Remember the read, snoozed and hidden entries before `action` changes
+    /// them.
+    fn checkpoint(&mut self, action: &'static str) {
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(Snapshot {
+            action,
+            read: self.read.clone(),
+            snoozed: self.snoozed.clone(),
+            hidden: self.hidden.clone(),
+            selected: self.get().map(|e| e.url.clone()),
+        });
+    }
+
+    /// Put back the read, snoozed and hidden entries from before the last
+    /// action, and select the entry that was selected then. Returns what the
+    /// action was, if there was one.
+    pub fn undo(&mut self) -> Option<&'static str> {
+        let snapshot = self.undo.pop()?;
+        self.read = snapshot.read;
+        self.snoozed = snapshot.snoozed;
+        self.hidden = snapshot.hidden;
+        self.apply_filter();
+        let (items, url) = (&self.items, snapshot.selected);
+        let selected = self.rows.iter().position(|row| match row {
+            Row::Entry(i) => Some(&items[*i].url) == url.as_ref(),
+            Row::Feed(_) | Row::Date(_) => false,
+        });
+        if selected.is_some() {
+            self.select(selected);
+        }
+        Some(snapshot.action)
+    }
+
+    fn authors(&self) -> Vec<Option<String>> {
+        let mut authors = self
+            .entries
+            .iter()
+            .map(|e| e.author.clone())
+            .collect::<Vec<_>>();
+        authors.sort_by(|a, b| match (a, b) {
+            (Some(a), Some(b)) => a
+                .to_lowercase()
+                .cmp(&b.to_lowercase())
+                .then_with(|| a.cmp(b)),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        authors.dedup();
+        authors
+    }
+
+    pub fn cycle_author(&mut self) {
+        let authors = self.authors();
+        self.author = match &self.author {
+            None => authors.first().cloned(),
+            Some(author) => authors.iter().skip_while(|a| *a != author).nth(1).cloned(),
+        };
+        self.apply_filter();
+    }
+
+    /// Only show the entries whose titles contain `query`, ignoring case.
+    pub fn set_search(&mut self, query: Option<String>) {
+        self.search = query.filter(|q| !q.is_empty());
+        self.apply_filter();
+    }
+
+    pub fn search(&self) -> Option<&str> {
+        self.search.as_deref()
+    }
+
+    pub fn title(&self) -> String {
+        let mut title = match &self.author {
+            None => "Feed Entries".to_string(),
+            Some(author) => format!("Feed Entries by {}", author.as_deref().unwrap_or("unknown")),
+        };
+        if let Some(feed) = self
+            .feeds
+            .iter()
+            .find(|f| Some(&f.url) == self.focus.as_ref())
+        {
+            title.push_str(&format!(" in {}", feed.title));
+        }
+        if let Some(search) = &self.search {
+            title.push_str(&format!(" matching {:?}", search));
+        }
+        let skipped = self.feeds.iter().map(|f| f.warnings.len()).sum::<usize>();
+        if skipped > 0 {
+            title.push_str(&format!(" ({} skipped)", skipped));
+        }
+        if !self.muting && !self.mute.is_empty() {
+            title.push_str(" (mutes off)");
+        }
+        title
+    }
+
+    /// The position of the selected row among the rows that can be selected,
+    /// counting from 1, and how many of them there are.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        let selectable = |row: &&Row| !matches!(row, Row::Date(_));
+        let selected = self.state.selected()?;
+        let position = self.rows[..=selected].iter().filter(selectable).count();
+        Some((position, self.rows.iter().filter(selectable).count()))
+    }
+
+    /// Select the next or previous row, skipping the date headings.
+    fn step(&mut self, forward: bool) {
+        let len = self.rows.len();
+        let start = match self.state.selected() {
+            Some(i) => i,
+            None => {
+                self.select(self.first_selectable());
+                return;
+            }
+        };
+        let mut i = start;
+        loop {
+            i = if forward {
+                if i + 1 < len {
+                    i + 1
+                } else if self.wrap_around {
+                    0
+                } else {
+                    return;
+                }
+            } else if i > 0 {
+                i - 1
+            } else if self.wrap_around {
+                len - 1
+            } else {
+                return;
+            };
+            if i == start {
+                return;
+            }
+            if !matches!(self.rows[i], Row::Date(_)) {
+                self.select(Some(i));
+                return;
+            }
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.step(true);
+    }
+
+    pub fn previous(&mut self) {
+        self.step(false);
+    }
+
+    fn jump_to_unread(&mut self, forward: bool) {
+        let len = self.rows.len();
+        let current = match self.state.selected() {
+            Some(i) => i,
+            None => return,
+        };
+        let is_unread = |i: usize| match self.rows[i] {
+            Row::Entry(j) => !self.is_read(&self.items[j]),
+            Row::Feed(_) | Row::Date(_) => false,
+        };
+        let found = if forward {
+            (current + 1..len)
+                .chain((0..current).filter(|_| self.wrap_around))
+                .find(|&i| is_unread(i))
+        } else {
+            (0..current)
+                .rev()
+                .chain((current + 1..len).rev().filter(|_| self.wrap_around))
+                .find(|&i| is_unread(i))
+        };
+        if let Some(i) = found {
+            self.select(Some(i));
+        }
+    }
+
+    pub fn next_unread(&mut self) {
+        self.jump_to_unread(true);
+    }
+
+    pub fn previous_unread(&mut self) {
+        self.jump_to_unread(false);
+    }
+
+    fn selected_feed(&self) -> Option<&str> {
+        match self.selected_row()? {
+            Row::Feed(i) => Some(&self.feeds[i].url),
+            Row::Entry(i) => Some(&self.items[i].feed_url),
+            Row::Date(_) => None,
+        }
+    }
+
+    fn jump_to_feed(&mut self, forward: bool) {
+        if self.view != View::Grouped {
+            return;
+        }
+        let current = match self.selected_feed() {
+            Some(url) => url.to_string(),
+            None => return,
+        };
+        let headers = self
+            .rows
+            .iter()
+            .filter_map(|row| match row {
+                Row::Feed(i) => Some(*i),
+                Row::Entry(_) | Row::Date(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let position = match headers.iter().position(|&i| self.feeds[i].url == current) {
+            Some(position) => position,
+            None => return,
+        };
+        let target = if forward {
+            if position + 1 < headers.len() {
+                position + 1
+            } else if self.wrap_around {
+                0
+            } else {
+                position
+            }
+        } else if position > 0 {
+            position - 1
+        } else if self.wrap_around {
+            headers.len() - 1
+        } else {
+            position
+        };
+
+        let feed = headers[target];
+        let url = &self.feeds[feed].url;
+        let remembered = self.positions.get(url).and_then(|entry_url| {
+            self.rows.iter().position(|row| match row {
+                Row::Entry(j) => {
+                    &self.items[*j].url == entry_url && &self.items[*j].feed_url == url
+                }
+                Row::Feed(_) | Row::Date(_) => false,
+            })
+        });
+        let header = self
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::Feed(i) if *i == feed));
+        self.select(remembered.or(header));
+    }
+
+    pub fn next_feed(&mut self) {
+        self.jump_to_feed(true);
+    }
+
+    pub fn previous_feed(&mut self) {
+        self.jump_to_feed(false);
+    }
+
+    fn selected_row(&self) -> Option<Row> {
+        self.state.selected().map(|i| self.rows[i])
+    }
+
+    pub fn get(&self) -> Option<&FeedListEntry> {
+        match self.selected_row() {
+            Some(Row::Entry(i)) => Some(&self.items[i]),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use prss::FeedEntry;
+
+    fn feed(title: &str, entries: &[(&str, u32)]) -> Feed {
+        Feed {
+            url: format!("https://example.com/{}.xml", title),
+            title: title.to_string(),
+            hub: None,
+            homepage: None,
+            description: None,
+            next_page: None,
+            update_interval: None,
+            entries: entries
+                .iter()
+                .map(|(title, day)| FeedEntry {
+                    title: title.to_string(),
+                    url: format!("https://example.com/{}", title),
+                    links: vec![format!("https://example.com/{}", title)],
+                    date: Utc.ymd(2021, 1, *day).and_hms(0, 0, 0),
+                    author: None,
+                    summary: None,
+                    image_url: None,
+                    comments: None,
+                    comments_url: None,
+                    categories: vec![],
+                })
+                .collect(),
+            warnings: vec![],
+            moved_to: None,
+        }
+    }
+
+    fn two_feeds() -> Vec<Feed> {
+        vec![feed("a", &[("a1", 1), ("a3", 3)]), feed("b", &[("b2", 2)])]
+    }
+
+    fn feedlist(feeds: Vec<Feed>, wrap_around: bool) -> FeedList {
+        let config = Config {
+            wrap_around,
+            ..Config::default()
+        };
+        FeedList::new(feeds, HashSet::new(), &config)
+    }
+
+    fn titles(feedlist: &FeedList) -> Vec<&str> {
+        feedlist.items.iter().map(|e| e.title.as_str()).collect()
+    }
+
+    fn selected(feedlist: &FeedList) -> Option<&str> {
+        feedlist.get().map(|e| e.title.as_str())
+    }
+
+    #[test]
+    fn sorted_newest_first() {
+        let feedlist = feedlist(
+            vec![
+                feed("a", &[("a1", 1), ("a3", 3)]),
+                feed("b", &[("b2", 2), ("b4", 4)]),
+            ],
+            true,
+        );
+        assert_eq!(titles(&feedlist), ["b4", "a3", "b2", "a1"]);
+        assert_eq!(selected(&feedlist), Some("b4"));
+    }
+
+    #[test]
+    fn ties_sorted_by_feed_and_title() {
+        let feedlist = feedlist(
+            vec![
+                feed("b", &[("y", 1), ("x", 1)]),
+                feed("a", &[("z", 1)]),
+                feed("c", &[("new", 2)]),
+            ],
+            true,
+        );
+        assert_eq!(titles(&feedlist), ["new", "z", "x", "y"]);
+    }
+
+    #[test]
+    fn wrap_around() {
+        let mut feedlist = feedlist(vec![feed("a", &[("a1", 1), ("a2", 2), ("a3", 3)])], true);
+        feedlist.previous();
+        assert_eq!(selected(&feedlist), Some("a1"));
+        feedlist.next();
+        assert_eq!(selected(&feedlist), Some("a3"));
+        feedlist.next();
+        assert_eq!(selected(&feedlist), Some("a2"));
+    }
+
+    #[test]
+    fn no_wrap_around() {
+        let mut feedlist = feedlist(vec![feed("a", &[("a1", 1), ("a2", 2)])], false);
+        feedlist.previous();
+        assert_eq!(selected(&feedlist), Some("a2"));
+        feedlist.next();
+        feedlist.next();
+        assert_eq!(selected(&feedlist), Some("a1"));
+    }
+
+    #[test]
+    fn single_entry() {
+        for &wrap_around in &[true, false] {
+            let mut feedlist = feedlist(vec![feed("a", &[("a1", 1)])], wrap_around);
+            feedlist.next();
+            assert_eq!(selected(&feedlist), Some("a1"));
+            feedlist.previous();
+            assert_eq!(selected(&feedlist), Some("a1"));
+        }
+    }
+
+    #[test]
+    fn unread_jumps() {
+        let mut feedlist = feedlist(vec![feed("a", &[("a1", 1), ("a2", 2), ("a3", 3)])], true);
+        feedlist.read.insert("https://example.com/a2".to_string());
+        feedlist.toggle_show_read();
+        assert_eq!(titles(&feedlist), ["a3", "a2", "a1"]);
+        feedlist.next_unread();
+        assert_eq!(selected(&feedlist), Some("a1"));
+        feedlist.next_unread();
+        assert_eq!(selected(&feedlist), Some("a3"));
+        feedlist.previous_unread();
+        assert_eq!(selected(&feedlist), Some("a1"));
+    }
+
+    #[test]
+    fn date_headers_skipped() {
+        let config = Config {
+            date_headers: true,
+            ..Config::default()
+        };
+        let mut feedlist = FeedList::new(
+            vec![feed("a", &[("a1", 1), ("a2", 2), ("a3", 2)])],
+            HashSet::new(),
+            &config,
+        );
+        let headings = feedlist
+            .rows
+            .iter()
+            .filter_map(|row| match row {
+                Row::Date(i) => Some(feedlist.date_heading(*i)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(headings.len(), 2);
+        assert_eq!(feedlist.rows.len(), 5);
+        assert_eq!(selected(&feedlist), Some("a2"));
+        feedlist.next();
+        feedlist.next();
+        assert_eq!(selected(&feedlist), Some("a1"));
+        feedlist.previous();
+        assert_eq!(selected(&feedlist), Some("a3"));
+        feedlist.next();
+        feedlist.next();
+        assert_eq!(selected(&feedlist), Some("a2"));
+        feedlist.previous();
+        assert_eq!(selected(&feedlist), Some("a1"));
+    }
+
+    #[test]
+    fn date_headers_after_midnight() {
+        let config = Config {
+            date_headers: true,
+            ..Config::default()
+        };
+        let mut feedlist = FeedList::new(
+            vec![feed("a", &[("a1", 1), ("a2", 2)])],
+            HashSet::new(),
+            &config,
+        );
+        feedlist.today = feedlist.today.pred();
+        feedlist.update_today();
+        assert_eq!(feedlist.today, Local::today().naive_local());
+    }
+
+    #[test]
+    fn authors_deduplicated() {
+        let mut a = feed("a", &[("a1", 1), ("a2", 2), ("a3", 3), ("a4", 4)]);
+        for (entry, author) in a.entries.iter_mut().zip(&["Jane", "jane", "Jane", "bob"]) {
+            entry.author = Some(author.to_string());
+        }
+        let feedlist = feedlist(vec![a], true);
+        assert_eq!(
+            feedlist.authors(),
+            [
+                Some("bob".to_string()),
+                Some("Jane".to_string()),
+                Some("jane".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn read_entries_at_bottom() {
+        let config = Config {
+            read_entries: ReadEntries::Bottom,
+            ..Config::default()
+        };
+        let read = vec!["https://example.com/a3".to_string()]
+            .into_iter()
+            .collect();
+        let feedlist = FeedList::new(
+            vec![feed("a", &[("a1", 1), ("a2", 2), ("a3", 3)])],
+            read,
+            &config,
+        );
+        assert_eq!(titles(&feedlist), ["a2", "a1", "a3"]);
+    }
+
+    #[test]
+    fn sort_direction() {
+        let mut feedlist = feedlist(two_feeds(), true);
+        assert_eq!(selected(&feedlist), Some("a3"));
+        feedlist.flip_sort_direction();
+        assert_eq!(titles(&feedlist), ["a1", "b2", "a3"]);
+        assert_eq!(selected(&feedlist), Some("a3"));
+
+        feedlist.set_sort(SortKey::Feed, false);
+        assert_eq!(titles(&feedlist), ["a3", "a1", "b2"]);
+        feedlist.flip_sort_direction();
+        assert_eq!(titles(&feedlist), ["b2", "a3", "a1"]);
+        assert_eq!(selected(&feedlist), Some("a3"));
+
+        // Titles sort from A to Z, also after sorting by newest first.
+        feedlist.set_sort(SortKey::Date, false);
+        feedlist.set_sort(SortKey::Title, false);
+        assert_eq!(titles(&feedlist), ["a1", "a3", "b2"]);
+    }
+
+    #[test]
+    fn reloaded_config() {
+        let mut feedlist = feedlist(two_feeds(), true);
+        feedlist.next();
+        feedlist.set_config(&Config {
+            mute: vec![Regex::new("^a1$").unwrap()],
+            sort_direction: Some(SortDirection::Ascending),
+            ..Config::default()
+        });
+        assert_eq!(titles(&feedlist), ["b2", "a3"]);
+        assert_eq!(selected(&feedlist), Some("b2"));
+    }
+
+    #[test]
+    fn due_reminders_first() {
+        let mut feedlist = feedlist(two_feeds(), true);
+        feedlist.read.insert("https://example.com/a1".to_string());
+        let now = Utc::now();
+        feedlist.set_reminders(
+            vec![
+                (
+                    "https://example.com/a1".to_string(),
+                    now - chrono::Duration::hours(1),
+                ),
+                (
+                    "https://example.com/b2".to_string(),
+                    now + chrono::Duration::hours(1),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(titles(&feedlist), ["a1", "a3", "b2"]);
+        assert_eq!(selected(&feedlist), Some("a1"));
+
+        feedlist.next();
+        feedlist.next();
+        feedlist.set_reminder(Some(now));
+        assert_eq!(titles(&feedlist), ["b2", "a1", "a3"]);
+        feedlist.set_reminder(None);
+        assert_eq!(titles(&feedlist), ["a1", "a3", "b2"]);
+    }
+
+    #[test]
+    fn snoozed_hidden() {
+        let mut feedlist = feedlist(two_feeds(), true);
+        let now = Utc::now();
+        feedlist.set_snoozed(
+            vec![(
+                "https://example.com/a1".to_string(),
+                now - chrono::Duration::hours(1),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(titles(&feedlist), ["a3", "b2", "a1"]);
+
+        feedlist.read.insert("https://example.com/a3".to_string());
+        feedlist.snooze_selected(now + chrono::Duration::hours(1));
+        assert_eq!(titles(&feedlist), ["b2", "a1"]);
+        assert_eq!(selected(&feedlist), Some("b2"));
+        assert!(!feedlist.read.contains("https://example.com/a3"));
+    }
+
+    #[test]
+    fn hidden_for_good() {
+        let mut feedlist = feedlist(two_feeds(), true);
+        feedlist.hide_selected();
+        assert_eq!(titles(&feedlist), ["b2", "a1"]);
+        assert_eq!(selected(&feedlist), Some("b2"));
+        assert!(!feedlist.read.contains("https://example.com/a3"));
+
+        feedlist.read.insert("https://example.com/a1".to_string());
+        feedlist.set_show_read(true);
+        assert_eq!(titles(&feedlist), ["b2", "a1"]);
+        feedlist.set_show_read(false);
+        assert_eq!(titles(&feedlist), ["b2"]);
+    }
+
+    #[test]
+    fn undo() {
+        let mut feedlist = feedlist(two_feeds(), false);
+        assert_eq!(feedlist.undo(), None);
+        feedlist.mark_selected_read_and_advance();
+        feedlist.hide_selected();
+        assert_eq!(titles(&feedlist), ["a1"]);
+
+        assert_eq!(feedlist.undo(), Some("hiding"));
+        assert_eq!(titles(&feedlist), ["b2", "a1"]);
+        assert_eq!(selected(&feedlist), Some("b2"));
+        assert_eq!(feedlist.undo(), Some("marking read"));
+        assert_eq!(titles(&feedlist), ["a3", "b2", "a1"]);
+        assert_eq!(selected(&feedlist), Some("a3"));
+        assert!(feedlist.read.is_empty());
+        assert_eq!(feedlist.undo(), None);
+
+        // Also when marked read by resting on the entry.
+        feedlist.mark_read_in_place("https://example.com/a3".to_string());
+        assert_eq!(feedlist.undo(), Some("marking read"));
+        assert!(feedlist.read.is_empty());
+    }
+
+    #[test]
+    fn merged_feeds() {
+        let mut feedlist = feedlist(two_feeds(), true);
+        feedlist.next();
+        feedlist.read.insert("https://example.com/a3".to_string());
+        let new = feedlist.set_feeds(vec![
+            feed("a", &[("a1", 1), ("a3", 3), ("a5", 5)]),
+            feed("b", &[("b2", 2), ("b4", 4)]),
+        ]);
+        assert_eq!(new, 2);
+        assert_eq!(titles(&feedlist), ["a5", "b4", "b2", "a1"]);
+        assert_eq!(selected(&feedlist), Some("b2"));
+        assert!(feedlist.read.contains("https://example.com/a3"));
+        assert_eq!(feedlist.set_feeds(vec![feed("b", &[("b2", 2)])]), 0);
+
+        // Grouped by feed, the rows of the old feeds don't fit the new ones.
+        let mut grouped = FeedList::new(two_feeds(), HashSet::new(), &Config::default());
+        grouped.toggle_view(View::Grouped);
+        grouped.next();
+        assert_eq!(grouped.selected_feed(), Some("https://example.com/b.xml"));
+        grouped.set_feeds(vec![feed("b", &[("b2", 2)]), feed("c", &[("c4", 4)])]);
+        assert_eq!(grouped.selected_feed(), Some("https://example.com/b.xml"));
+    }
+
+    #[test]
+    fn refresh_without_a_feed() {
+        for view in &[View::Grouped, View::Summary] {
+            let mut feedlist = feedlist(
+                vec![
+                    feed("a", &[("a3", 3)]),
+                    feed("b", &[("b2", 2)]),
+                    feed("c", &[("c1", 1)]),
+                ],
+                true,
+            );
+            feedlist.toggle_view(*view);
+            feedlist.next();
+            feedlist.next();
+            assert_eq!(feedlist.selected_feed(), Some("https://example.com/c.xml"));
+            // Fetching a failed for the moment.
+            feedlist.set_feeds(vec![feed("b", &[("b2", 2)]), feed("c", &[("c1", 1)])]);
+            assert_eq!(feedlist.selected_feed(), Some("https://example.com/c.xml"));
+        }
+    }
+
+    #[test]
+    fn folded() {
+        assert_eq!(fold("STRASSE"), fold("Straße"));
+        assert_eq!(fold("ÉTÉ"), fold("été"));
+        assert_eq!(fold("e\u{301}te\u{301}"), fold("\u{e9}t\u{e9}"));
+        assert_eq!(fold("İSTANBUL"), fold("istanbul"));
+        assert_ne!(fold("ıroutine"), fold("iroutine"));
+        assert_eq!(fold("ΣΟΦΊΑ"), fold("σοφία"));
+        assert_eq!(fold("МОСКВА"), fold("москва"));
+        assert_ne!(fold("resume"), fold("résumé"));
+    }
+
+    #[test]
+    fn search() {
+        let mut feedlist = feedlist(
+            vec![
+                feed("a", &[("Große Straße", 1), ("Ärger in İzmir", 2)]),
+                feed("b", &[("Новости дня", 3), ("Café olé", 4)]),
+            ],
+            true,
+        );
+        let search = |feedlist: &mut FeedList, query: &str| {
+            feedlist.set_search(Some(query.to_string()));
+            titles(feedlist)
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search(&mut feedlist, "STRASSE"), ["Große Straße"]);
+        assert_eq!(search(&mut feedlist, "ärger"), ["Ärger in İzmir"]);
+        assert_eq!(search(&mut feedlist, "İZMİR"), ["Ärger in İzmir"]);
+        assert_eq!(search(&mut feedlist, "izmir"), ["Ärger in İzmir"]);
+        assert_eq!(search(&mut feedlist, "новости"), ["Новости дня"]);
+        assert_eq!(search(&mut feedlist, "CAFÉ OLÉ"), ["Café olé"]);
+        assert!(search(&mut feedlist, "cafe").is_empty());
+        assert!(feedlist.title().contains("matching \"cafe\""));
+
+        feedlist.set_search(Some(String::new()));
+        assert_eq!(feedlist.search(), None);
+        assert_eq!(titles(&feedlist).len(), 4);
+    }
+
+    #[test]
+    fn position() {
+        let mut feedlist = feedlist(two_feeds(), true);
+        assert_eq!(feedlist.position(), Some((1, 3)));
+        feedlist.previous();
+        assert_eq!(feedlist.position(), Some((3, 3)));
+    }
+
+    #[test]
+    fn recent_unread() {
+        let mut feedlist = feedlist(two_feeds(), true);
+        feedlist.set_sort(SortKey::Title, false);
+        feedlist.mark_read(vec!["https://example.com/a3".to_string()]);
+        let recent = feedlist.recent_unread(5);
+        let titles = recent.iter().map(|e| e.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, ["b2", "a1"]);
+        assert_eq!(feedlist.recent_unread(1).len(), 1);
+    }
+
+    #[test]
+    fn empty() {
+        let mut feedlist = feedlist(vec![feed("a", &[])], true);
+        assert_eq!(selected(&feedlist), None);
+        assert_eq!(feedlist.position(), None);
+        feedlist.next();
+        feedlist.previous();
+        assert_eq!(feedlist.state.selected(), None);
+        assert_eq!(selected(&feedlist), None);
+    }
+}
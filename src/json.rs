@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::feedlist::FeedListEntry;
+
+/// Bumped whenever a field is removed or changes meaning. New fields may be
+/// added without changing the version.
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Output<'a> {
+    version: u32,
+    entries: Vec<Entry<'a>>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    feed_title: &'a str,
+    entry_title: &'a str,
+    url: &'a str,
+    /// RFC 3339
+    date: String,
+    read: bool,
+    author: Option<&'a str>,
+    summary: Option<&'a str>,
+    image_url: Option<&'a str>,
+    comments: Option<u64>,
+    comments_url: Option<&'a str>,
+    categories: &'a [String],
+}
+
+fn output<'a>(
+    entries: impl IntoIterator<Item = &'a FeedListEntry>,
+    read: &HashSet<String>,
+) -> Output<'a> {
+    Output {
+        version: VERSION,
+        entries: entries
+            .into_iter()
+            .map(|entry| Entry {
+                feed_title: &entry.feed_title,
+                entry_title: &entry.title,
+                url: &entry.url,
+                date: entry.date.to_rfc3339(),
+                read: read.contains(&entry.url),
+                author: entry.author.as_deref(),
+                summary: entry.summary.as_deref(),
+                image_url: entry.image_url.as_deref(),
+                comments: entry.comments,
+                comments_url: entry.comments_url.as_deref(),
+                categories: &entry.categories,
+            })
+            .collect(),
+    }
+}
+
+pub fn print_json<'a>(
+    entries: impl IntoIterator<Item = &'a FeedListEntry>,
+    read: &HashSet<String>,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    serde_json::to_writer_pretty(&mut out, &output(entries, read))?;
+    writeln!(out)
+}
+
+/// The urls of the read entries in a JSON export of another reader, or of
+/// `--json`: a list of urls, or of objects with a `url` and an optional `read`
+/// flag, either on its own or as the `entries` or `items` of an object.
+pub fn read_urls(content: &str) -> serde_json::Result<Vec<String>> {
+    let value: Value = serde_json::from_str(content)?;
+    let items = match &value {
+        Value::Object(object) => object.get("entries").or_else(|| object.get("items")),
+        _ => Some(&value),
+    };
+    Ok(items
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(read_url).collect())
+        .unwrap_or_default())
+}
+
+fn read_url(item: &Value) -> Option<String> {
+    match item {
+        Value::String(url) => Some(url.clone()),
+        Value::Object(object) if object.get("read").and_then(Value::as_bool) != Some(false) => {
+            object.get("url").and_then(Value::as_str).map(String::from)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    #[test]
+    fn shape() {
+        let entry = FeedListEntry {
+            title: "Hello world".to_string(),
+            feed_title: "Example Feed".to_string(),
+            feed_url: "https://example.com/feed.xml".to_string(),
+            hub: None,
+            homepage: None,
+            url: "https://example.com/hello".to_string(),
+            links: vec!["https://example.com/hello".to_string()],
+            date: Utc.ymd(2021, 2, 3).and_hms(10, 0, 0),
+            author: None,
+            summary: Some("<p>Hi</p>".to_string()),
+            image_url: Some("https://example.com/hello.jpg".to_string()),
+            comments: Some(3),
+            comments_url: None,
+            categories: vec!["News".to_string()],
+        };
+        let read = vec!["https://example.com/hello".to_string()]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            serde_json::to_value(output(&[entry], &read)).unwrap(),
+            json!({
+                "version": 1,
+                "entries": [{
+                    "feed_title": "Example Feed",
+                    "entry_title": "Hello world",
+                    "url": "https://example.com/hello",
+                    "date": "2021-02-03T10:00:00+00:00",
+                    "read": true,
+                    "author": null,
+                    "summary": "<p>Hi</p>",
+                    "image_url": "https://example.com/hello.jpg",
+                    "comments": 3,
+                    "comments_url": null,
+                    "categories": ["News"],
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn imported_urls() {
+        assert_eq!(
+            read_urls(r#"["https://example.com/a", "https://example.com/b"]"#).unwrap(),
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        let export = r#"{
+            "version": 1,
+            "entries": [
+                {"url": "https://example.com/a", "read": true},
+                {"url": "https://example.com/b", "read": false},
+                {"url": "https://example.com/c"}
+            ]
+        }"#;
+        assert_eq!(
+            read_urls(export).unwrap(),
+            ["https://example.com/a", "https://example.com/c"]
+        );
+        assert!(read_urls("not json").is_err());
+    }
+}
@@ -0,0 +1,1579 @@
+//! Fetching, caching and parsing of Atom and RSS feeds, as used by the prss
+//! terminal feed reader.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, metadata, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use atom_syndication as atom;
+use chrono::{DateTime, FixedOffset, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rss::extension::syndication::UpdatePeriod;
+use serde::Deserialize;
+use tokio::time::Instant;
+
+mod error;
+
+use error::cache_error;
+pub use error::{Error, Result};
+
+const HOST_REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const SYNDICATION_NAMESPACE: &str = "http://purl.org/rss/1.0/modules/syndication/";
+/// What `read_rss` declares the syndication module as instead.
+const RENAMED_SYNDICATION_NAMESPACE: &str = "urn:prss:syndication";
+/// The most redirects followed for one request, as many as reqwest follows.
+const MAX_REDIRECTS: usize = 10;
+
+/// How feed bodies are stored in the cache.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// How feeds are fetched and cached between runs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FetchOptions {
+    pub compression: CacheCompression,
+    /// Use the cached copy if it is younger than this, without asking the server.
+    pub min_refresh: Option<Duration>,
+    /// Fetch the feed again if the cached copy is older than this, even if the
+    /// server says it hasn't changed.
+    pub max_age: Option<Duration>,
+    /// How many further pages of paginated Atom feeds to follow.
+    pub next_pages: usize,
+    /// Keep at most this many entries of each feed, including those of further
+    /// pages.
+    pub max_entries: Option<usize>,
+    /// Give up on fetching a feed, including its further pages, after this long.
+    pub timeout: Option<Duration>,
+    /// Try to read the feed as this format first, whatever the server says it
+    /// is.
+    pub format: Option<FeedFormat>,
+}
+
+impl CacheCompression {
+    const ALL: [CacheCompression; 3] = [
+        CacheCompression::Zstd,
+        CacheCompression::Gzip,
+        CacheCompression::None,
+    ];
+
+    fn extension(self) -> &'static str {
+        match self {
+            CacheCompression::None => "",
+            CacheCompression::Gzip => ".gz",
+            CacheCompression::Zstd => ".zst",
+        }
+    }
+
+    fn file_name(self, digest: md5::Digest) -> String {
+        format!("{:x}{}", digest, self.extension())
+    }
+}
+
+/// A single entry of a feed.
+#[derive(Clone)]
+pub struct FeedEntry {
+    pub title: String,
+    pub url: String,
+    /// All links to the entry, in other languages or formats, starting with
+    /// `url`.
+    pub links: Vec<String>,
+    pub date: DateTime<Utc>,
+    pub author: Option<String>,
+    /// The summary or description of the entry, usually HTML.
+    pub summary: Option<String>,
+    /// A thumbnail or other image of the entry, from Media RSS or an image
+    /// enclosure.
+    pub image_url: Option<String>,
+    /// The number of comments on the entry, from `slash:comments` or
+    /// `thr:total`.
+    pub comments: Option<u64>,
+    /// The page with the comments on the entry, from the RSS `comments`
+    /// element or an Atom `replies` link, as on aggregators like Hacker News.
+    pub comments_url: Option<String>,
+    pub categories: Vec<String>,
+}
+
+/// A parsed Atom or RSS feed.
+pub struct Feed {
+    pub url: String,
+    pub title: String,
+    pub hub: Option<String>,
+    /// The website the feed belongs to.
+    pub homepage: Option<String>,
+    /// The Atom subtitle or RSS description of the feed.
+    pub description: Option<String>,
+    /// The next page of a paginated Atom feed, from its rel="next" link.
+    pub next_page: Option<String>,
+    /// How often the feed says it is updated, from the RSS syndication module.
+    pub update_interval: Option<Duration>,
+    pub entries: Vec<FeedEntry>,
+    /// Why entries that couldn't be read were left out.
+    pub warnings: Vec<String>,
+    /// Where the feed has moved to, when fetching it was redirected there
+    /// permanently, such as from http to https.
+    pub moved_to: Option<String>,
+}
+
+fn hub_link(links: &[atom::Link]) -> Option<String> {
+    links
+        .iter()
+        .find(|link| link.rel() == "hub")
+        .map(|link| link.href().to_string())
+}
+
+/// How often the channel says it is updated in the syndication module, unless
+/// the module has invalid values.
+fn update_interval(channel: &rss::Channel) -> Option<Duration> {
+    let prefix = channel
+        .namespaces()
+        .iter()
+        .find(|(_, namespace)| *namespace == RENAMED_SYNDICATION_NAMESPACE)
+        .map(|(prefix, _)| prefix)?;
+    let module = channel.extensions().get(prefix)?;
+    let value = |name| {
+        module
+            .get(name)
+            .and_then(|values| values.first())
+            .and_then(|value| value.value())
+            .map(str::trim)
+    };
+    let period = match value("updatePeriod") {
+        Some(period) => period.parse().ok()?,
+        None => UpdatePeriod::DAILY,
+    };
+    let frequency: u64 = match value("updateFrequency") {
+        Some(frequency) => frequency.parse().ok()?,
+        None => 1,
+    };
+    let period: u64 = match period {
+        UpdatePeriod::HOURLY => 60 * 60,
+        UpdatePeriod::DAILY => 24 * 60 * 60,
+        UpdatePeriod::WEEKLY => 7 * 24 * 60 * 60,
+        UpdatePeriod::MONTHLY => 30 * 24 * 60 * 60,
+        UpdatePeriod::YEARLY => 365 * 24 * 60 * 60,
+    };
+    period.checked_div(frequency).map(Duration::from_secs)
+}
+
+/// Parse a date as found in a feed.
+///
+/// Accepts RFC 2822 dates, as used by RSS, and RFC 3339 dates, as used by Atom
+/// and JSON Feed, also without seconds or just a day, as in the `dc:date` of
+/// RSS 1.0. Common deviations from the standards, like using `UTC` as a
+/// time zone, are tolerated. Atom dates are already parsed by
+/// `atom_syndication`, so this is mostly needed for RSS.
+pub fn parse_feed_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    DateTime::parse_from_rfc2822(date)
+        .or_else(|_| DateTime::parse_from_rfc2822(&date.replace("UTC", "+0000")))
+        .or_else(|_| DateTime::parse_from_rfc3339(date))
+        .or_else(|_| {
+            let date = date
+                .strip_suffix('Z')
+                .map_or(date.to_string(), |d| d.to_string() + "+00:00");
+            DateTime::parse_from_str(&date, "%Y-%m-%dT%H:%M%:z")
+        })
+        .ok()
+        .map(DateTime::<Utc>::from)
+        // dc:date may be just a day.
+        .or_else(|| {
+            let day = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+            Some(DateTime::from_utc(day.and_hms(0, 0, 0), Utc))
+        })
+}
+
+/// The feed formats that prss can parse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    /// Guess the format of a feed from the `Content-Type` it was served with.
+    pub fn from_content_type(content_type: &str) -> Option<FeedFormat> {
+        let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "application/atom+xml" => Some(FeedFormat::Atom),
+            "application/rss+xml" | "application/rdf+xml" => Some(FeedFormat::Rss),
+            _ => None,
+        }
+    }
+
+    fn read(self, url: &str, content: &[u8]) -> Result<Feed> {
+        match self {
+            FeedFormat::Atom => read_atom(url, content),
+            FeedFormat::Rss => read_rss(url, content),
+        }
+    }
+}
+
+impl fmt::Display for FeedFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeedFormat::Atom => write!(f, "Atom"),
+            FeedFormat::Rss => write!(f, "RSS"),
+        }
+    }
+}
+
+/// The links to the entry itself, which are the ones with rel="alternate" (the
+/// default when rel is left out), or the first link if there are none.
+fn entry_links(base: &str, links: &[atom::Link]) -> Vec<String> {
+    let alternates = links
+        .iter()
+        .filter(|link| link.rel() == "alternate")
+        .collect::<Vec<_>>();
+    let links = if alternates.is_empty() {
+        links.iter().take(1).collect()
+    } else {
+        alternates
+    };
+    links
+        .into_iter()
+        .map(|link| resolve_link(base, link.href()).unwrap_or_else(|| link.href.clone()))
+        .collect()
+}
+
+/// The `replies` link of an Atom entry, preferring a web page over a feed of
+/// the comments.
+fn replies_link(links: &[atom::Link]) -> Option<&str> {
+    let replies = links.iter().filter(|link| link.rel() == "replies");
+    replies
+        .clone()
+        .find(|link| link.mime_type() == Some("text/html"))
+        .or_else(|| replies.clone().next())
+        .map(|link| link.href())
+}
+
+/// Titles are often HTML, so entities like &amp; survive XML parsing.
+/// Summaries are HTML, and are decoded when their text is shown instead.
+fn decode_title(title: &str) -> String {
+    html_escape::decode_html_entities(title).into_owned()
+}
+
+/// Resolve `href` relative to the feed at `base`, which may be a local path.
+fn resolve_link(base: &str, href: &str) -> Option<String> {
+    match (reqwest::Url::parse(base), reqwest::Url::parse(href)) {
+        (_, Ok(href)) => Some(href.to_string()),
+        (Ok(base), Err(_)) => base.join(href).ok().map(|url| url.to_string()),
+        (Err(_), Err(_)) => Path::new(base)
+            .parent()
+            .map(|dir| dir.join(href).to_string_lossy().into_owned()),
+    }
+}
+
+/// The `xml:base` of the root element of an Atom feed, which relative links
+/// are resolved against. atom_syndication doesn't keep it.
+fn xml_base(content: &[u8]) -> Option<String> {
+    let base =
+        regex::bytes::Regex::new(r#"<feed\b[^>]*\sxml:base\s*=\s*["']([^"']*)["']"#).unwrap();
+    let value = base.captures(content)?.get(1)?.as_bytes();
+    Some(html_escape::decode_html_entities(&String::from_utf8_lossy(value)).into_owned())
+}
+
+/// The parts of Atom and RSS extension elements that are read, such as to
+/// find images.
+trait ExtensionElement: Sized {
+    fn attr(&self, name: &str) -> Option<&str>;
+    fn children(&self, name: &str) -> &[Self];
+    fn value(&self) -> Option<&str>;
+}
+
+impl ExtensionElement for atom::extension::Extension {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(String::as_str)
+    }
+
+    fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    fn children(&self, name: &str) -> &[Self] {
+        self.children
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+impl ExtensionElement for rss::extension::Extension {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(String::as_str)
+    }
+
+    fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    fn children(&self, name: &str) -> &[Self] {
+        self.children
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// The image of an entry from its Media RSS elements: the first
+/// `media:thumbnail`, or else the first `media:content` that is an image, also
+/// looking inside `media:group`, as used by YouTube.
+fn media_image<E: ExtensionElement>(media: Option<&HashMap<String, Vec<E>>>) -> Option<String> {
+    let media = media?;
+    let top = |name: &str| media.get(name).map(Vec::as_slice).unwrap_or_default();
+    let elements = |name: &'static str| {
+        top(name).iter().chain(
+            top("group")
+                .iter()
+                .flat_map(move |group| group.children(name)),
+        )
+    };
+    let is_image = |e: &&E| {
+        e.attr("medium") == Some("image") || e.attr("type").is_some_and(|t| t.starts_with("image/"))
+    };
+    elements("thumbnail")
+        .chain(elements("content").filter(is_image))
+        .find_map(|e| e.attr("url"))
+        .map(String::from)
+}
+
+/// The number of comments on an entry, from `slash:comments`, as used by
+/// forums and news sites, or the Atom threading extension's `thr:total`.
+fn comment_count<E: ExtensionElement>(
+    extensions: &HashMap<String, HashMap<String, Vec<E>>>,
+) -> Option<u64> {
+    [("slash", "comments"), ("thr", "total")]
+        .iter()
+        .find_map(|(prefix, name)| {
+            extensions
+                .get(*prefix)?
+                .get(*name)?
+                .first()?
+                .value()?
+                .trim()
+                .parse()
+                .ok()
+        })
+}
+
+fn skipped(title: &str, reason: &str) -> String {
+    format!("Skipped entry {:?}: {}", title, reason)
+}
+
+fn read_atom(url: &str, content: &[u8]) -> Result<Feed> {
+    let feed = atom::Feed::read_from(content).map_err(|e| Error::Parse {
+        format: FeedFormat::Atom,
+        source: Box::new(e),
+    })?;
+    let base = xml_base(content)
+        .and_then(|base| resolve_link(url, &base))
+        .unwrap_or_else(|| url.to_string());
+    let mut entries = vec![];
+    let mut warnings = vec![];
+    for e in feed.entries() {
+        let links = entry_links(&base, e.links());
+        match links.first() {
+            Some(link) => entries.push(FeedEntry {
+                title: decode_title(e.title()),
+                url: link.clone(),
+                links: links.clone(),
+                author: e.authors().first().map(|p| p.name().to_string()),
+                summary: e.summary().map(String::from),
+                image_url: media_image(e.extensions().get("media"))
+                    .or_else(|| {
+                        e.links()
+                            .iter()
+                            .find(|link| {
+                                link.rel() == "enclosure"
+                                    && link.mime_type().is_some_and(|t| t.starts_with("image/"))
+                            })
+                            .map(|link| link.href().to_string())
+                    })
+                    .and_then(|image| resolve_link(&base, &image)),
+                comments: comment_count(e.extensions()),
+                comments_url: replies_link(e.links()).and_then(|link| resolve_link(&base, link)),
+                categories: e
+                    .categories()
+                    .iter()
+                    .map(|c| c.label().unwrap_or_else(|| c.term()).to_string())
+                    .collect(),
+                date: DateTime::<Utc>::from(e.published.unwrap_or(e.updated)),
+            }),
+            None => warnings.push(skipped(e.title(), "no link")),
+        }
+    }
+    Ok(Feed {
+        url: url.to_string(),
+        title: decode_title(feed.title()),
+        description: feed.subtitle().map(decode_title),
+        next_page: feed
+            .links()
+            .iter()
+            .find(|link| link.rel() == "next")
+            .and_then(|link| resolve_link(&base, link.href())),
+        hub: hub_link(feed.links()),
+        homepage: feed
+            .links()
+            .iter()
+            .find(|link| link.rel() == "alternate")
+            .and_then(|link| resolve_link(&base, link.href())),
+        update_interval: None,
+        entries,
+        warnings,
+        moved_to: None,
+    })
+}
+
+fn read_rss(url: &str, content: &[u8]) -> Result<Feed> {
+    // The rss crate panics on invalid values of the syndication module, such
+    // as an unknown sy:updatePeriod. The module is declared under another
+    // namespace instead, so that the crate leaves it to `update_interval`.
+    let declaration =
+        regex::bytes::Regex::new(r#"(xmlns:[^\s=]+\s*=\s*)("([^"]*)"|'([^']*)')"#).unwrap();
+    let content = declaration.replace_all(content, |found: &regex::bytes::Captures| {
+        let value = found.get(3).or_else(|| found.get(4)).unwrap().as_bytes();
+        // The namespace may have been written with character references.
+        let value = html_escape::decode_html_entities(&String::from_utf8_lossy(value)).into_owned();
+        if value == SYNDICATION_NAMESPACE {
+            [
+                &found[1],
+                b"\"",
+                RENAMED_SYNDICATION_NAMESPACE.as_bytes(),
+                b"\"",
+            ]
+            .concat()
+        } else {
+            found[0].to_vec()
+        }
+    });
+    let channel = rss::Channel::read_from(&content[..]).map_err(|e| Error::Parse {
+        format: FeedFormat::Rss,
+        source: Box::new(e),
+    })?;
+    let mut entries = vec![];
+    let mut warnings = vec![];
+    for i in channel.items() {
+        let title = i.title().unwrap_or("");
+        // Items may only be linked to through a guid that is a permalink.
+        let permalink = i
+            .guid()
+            .filter(|guid| guid.is_permalink())
+            .map(|guid| guid.value())
+            .filter(|guid| guid.starts_with("http://") || guid.starts_with("https://"));
+        let link = match i.link().or(permalink) {
+            Some(link) => link,
+            None => {
+                warnings.push(skipped(title, "no link"));
+                continue;
+            }
+        };
+        // RSS 1.0 (RDF) feeds date their items with dc:date instead.
+        let pub_date = i.pub_date().or_else(|| {
+            i.dublin_core_ext()
+                .and_then(|dc| dc.dates().first())
+                .map(String::as_str)
+        });
+        let date = match pub_date.and_then(parse_feed_date) {
+            Some(date) => date,
+            None => {
+                warnings.push(skipped(
+                    title,
+                    &format!("couldn't parse date {:?}", pub_date),
+                ));
+                continue;
+            }
+        };
+        entries.push(FeedEntry {
+            title: decode_title(title),
+            url: link.to_string(),
+            links: vec![link.to_string()],
+            author: i.author().map(String::from).or_else(|| {
+                i.dublin_core_ext()
+                    .and_then(|dc| dc.creators().first().cloned())
+            }),
+            summary: i.description().map(String::from),
+            image_url: media_image(i.extensions().get("media"))
+                .or_else(|| {
+                    i.enclosure()
+                        .filter(|enclosure| enclosure.mime_type().starts_with("image/"))
+                        .map(|enclosure| enclosure.url().to_string())
+                })
+                .and_then(|image| resolve_link(url, &image)),
+            comments: comment_count(i.extensions()),
+            comments_url: i.comments().and_then(|link| resolve_link(url, link)),
+            categories: i
+                .categories()
+                .iter()
+                .map(|c| c.name().to_string())
+                .collect(),
+            date,
+        });
+    }
+    Ok(Feed {
+        url: url.to_string(),
+        title: decode_title(channel.title()),
+        description: Some(decode_title(channel.description())).filter(|d| !d.is_empty()),
+        next_page: None,
+        hub: channel.atom_ext().and_then(|ext| hub_link(ext.links())),
+        homepage: Some(channel.link().to_string()).filter(|link| !link.is_empty()),
+        update_interval: update_interval(&channel),
+        entries,
+        warnings,
+        moved_to: None,
+    })
+}
+
+/// Parse an Atom or RSS document fetched from `url`.
+pub fn read_feed(url: &str, content: &[u8]) -> Result<Feed> {
+    read_feed_with_hint(url, content, None)
+}
+
+/// Parse an Atom or RSS document fetched from `url`, trying the `hint` format
+/// first.
+///
+/// The other formats are still tried if the document can't be parsed as `hint`,
+/// since servers don't always report the right content type.
+pub fn read_feed_with_hint(url: &str, content: &[u8], hint: Option<FeedFormat>) -> Result<Feed> {
+    let formats = match hint {
+        Some(FeedFormat::Rss) => [FeedFormat::Rss, FeedFormat::Atom],
+        _ => [FeedFormat::Atom, FeedFormat::Rss],
+    };
+    let mut hint_error = None;
+    for format in formats.iter() {
+        match format.read(url, content) {
+            Ok(feed) => return Ok(feed),
+            Err(e) if Some(*format) == hint => hint_error = Some(e),
+            Err(_) => {}
+        }
+    }
+    Err(Error::UnsupportedFormat {
+        url: url.to_string(),
+        served_as: hint_error.map(Box::new),
+    })
+}
+
+type LastRequest = Arc<tokio::sync::Mutex<Option<Instant>>>;
+
+/// Spaces out requests to the same host when fetching feeds concurrently.
+#[derive(Clone, Default)]
+struct HostThrottle {
+    hosts: Arc<Mutex<HashMap<String, LastRequest>>>,
+}
+
+impl HostThrottle {
+    /// Wait until it is polite to send another request to the host of `url`.
+    async fn wait(&self, url: &str) {
+        let host = match reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+        {
+            Some(host) => host,
+            None => return,
+        };
+        let last = self.hosts.lock().unwrap().entry(host).or_default().clone();
+        let mut last = last.lock().await;
+        if let Some(last) = *last {
+            tokio::time::sleep_until(last + HOST_REQUEST_INTERVAL).await;
+        }
+        *last = Some(Instant::now());
+    }
+}
+
+/// The prss directories below the XDG base directories.
+pub fn base_directories() -> Result<xdg::BaseDirectories> {
+    Ok(xdg::BaseDirectories::with_prefix("prss")?)
+}
+
+/// The form of `url` used to look it up in the cache, so that equivalent urls
+/// share one cached copy. The scheme and host are lowercased, default ports
+/// and fragments are dropped and query parameters are sorted. The path is left
+/// alone, as servers may treat e.g. a trailing slash differently.
+pub fn normalize_url(url: &str) -> String {
+    let mut parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return url.to_string(),
+    };
+    parsed.set_fragment(None);
+    if parsed.query().is_some() {
+        let mut pairs = parsed
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        if pairs.is_empty() {
+            parsed.set_query(None);
+        } else {
+            parsed.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+    }
+    parsed.to_string()
+}
+
+/// The digest covers the whole normalized url, so distinct feeds only share a
+/// cache file in the case of a full md5 collision.
+fn cache_digest(url: &str) -> md5::Digest {
+    md5::compute(normalize_url(url))
+}
+
+fn find_cache_file(xdg_dirs: &xdg::BaseDirectories, digest: md5::Digest) -> Option<PathBuf> {
+    CacheCompression::ALL
+        .iter()
+        .find_map(|compression| xdg_dirs.find_cache_file(compression.file_name(digest)))
+}
+
+/// Write the cache file through a temporary file that is unique to this write,
+/// so concurrent writes of the same feed never see each other's partial
+/// contents and the last rename wins.
+fn write_cache_file(path: &Path, content: &[u8], compression: CacheCompression) -> Result<()> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let tmp = path.with_file_name(format!(
+        "{}.{}.{}.tmp",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let write = || -> std::io::Result<()> {
+        let f = File::create(&tmp)?;
+        match compression {
+            CacheCompression::None => {
+                let mut f = f;
+                f.write_all(content)?;
+            }
+            CacheCompression::Gzip => {
+                let mut encoder = GzEncoder::new(f, flate2::Compression::default());
+                encoder.write_all(content)?;
+                encoder.finish()?;
+            }
+            CacheCompression::Zstd => zstd::stream::copy_encode(content, f, 0)?,
+        }
+        Ok(())
+    };
+    write().map_err(cache_error(&tmp))?;
+    fs::rename(&tmp, path).map_err(cache_error(path))
+}
+
+fn read_cache_bytes(path: &Path) -> Result<Vec<u8>> {
+    let mut buf = fs::read(path).map_err(cache_error(path))?;
+    if buf.starts_with(&GZIP_MAGIC) {
+        let mut decompressed = vec![];
+        GzDecoder::new(&buf[..])
+            .read_to_end(&mut decompressed)
+            .map_err(cache_error(path))?;
+        buf = decompressed;
+    } else if buf.starts_with(&ZSTD_MAGIC) {
+        buf = zstd::stream::decode_all(&buf[..]).map_err(cache_error(path))?;
+    }
+    Ok(buf)
+}
+
+fn read_cache_file(url: &str, path: &Path, format: Option<FeedFormat>) -> Result<Feed> {
+    read_feed_with_hint(url, &read_cache_bytes(path)?[..], format)
+}
+
+/// The path of the cached copy of the feed at `url`, whose name is a hash of
+/// the url.
+pub fn cached_feed_path(url: &str) -> Result<Option<PathBuf>> {
+    let xdg_dirs = base_directories()?;
+    Ok(find_cache_file(&xdg_dirs, cache_digest(url)))
+}
+
+/// The path and the raw, decompressed contents of the cached copy of the feed
+/// at `url`, for troubleshooting feeds that fail to parse.
+pub fn get_cached_feed_body(url: &str) -> Result<Option<(PathBuf, Vec<u8>)>> {
+    cached_feed_path(url)?
+        .map(|cache| Ok((cache.clone(), read_cache_bytes(&cache)?)))
+        .transpose()
+}
+
+/// The path of the feed at `url` if it is a local file, given either as a
+/// file:// url or as a plain path.
+pub fn local_feed_path(url: &str) -> Option<PathBuf> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "file" => parsed.to_file_path().ok(),
+        Ok(_) => None,
+        Err(_) => Some(PathBuf::from(url)),
+    }
+}
+
+fn read_local_feed(url: &str, path: &Path, format: Option<FeedFormat>) -> Result<Feed> {
+    let content = fs::read(path).map_err(|source| Error::LocalFeed {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    read_feed_with_hint(url, &content[..], format)
+}
+
+fn has_room(feed: &Feed, options: &FetchOptions) -> bool {
+    options
+        .max_entries
+        .is_none_or(|max_entries| feed.entries.len() < max_entries)
+}
+
+/// The next page to read after `feed`, unless it has been read already.
+fn next_page(feed: &Feed, visited: &[String]) -> Option<String> {
+    feed.next_page
+        .clone()
+        .filter(|next| !visited.contains(next))
+}
+
+fn append_page(feed: &mut Feed, page: Feed) {
+    let known = feed
+        .entries
+        .iter()
+        .map(|e| e.url.clone())
+        .collect::<std::collections::HashSet<_>>();
+    feed.entries
+        .extend(page.entries.into_iter().filter(|e| !known.contains(&e.url)));
+    feed.warnings.extend(page.warnings);
+    feed.next_page = page.next_page;
+}
+
+fn truncate_entries(feed: &mut Feed, options: &FetchOptions) {
+    if let Some(max_entries) = options.max_entries {
+        feed.entries.truncate(max_entries);
+    }
+}
+
+/// Remove the cache files of feeds other than `urls` and their cached further
+/// pages, as well as any cache file last written longer than `older_than` ago.
+/// Returns the number of files removed and their total size in bytes.
+pub fn purge_cache(urls: &[String], older_than: Option<Duration>) -> Result<(usize, u64)> {
+    let xdg_dirs = base_directories()?;
+    let mut keep = std::collections::HashSet::new();
+    for url in urls {
+        // Lists of feeds fetched with --feeds are cached by the digest of the
+        // url as given.
+        keep.insert(format!("{:x}", md5::compute(url)));
+        let mut visited = vec![];
+        let mut next = Some(url.clone());
+        while let Some(url) = next.take().filter(|url| !visited.contains(url)) {
+            keep.insert(format!("{:x}", cache_digest(&url)));
+            next = get_cached_feed_page(&url, None)
+                .ok()
+                .flatten()
+                .and_then(|page| page.next_page);
+            visited.push(url);
+        }
+    }
+
+    let (mut removed, mut freed) = (0, 0);
+    let cache_home = xdg_dirs.get_cache_home();
+    let dir = match fs::read_dir(&cache_home) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(e) => return Err(cache_error(cache_home)(e)),
+    };
+    for file in dir {
+        let file = file.map_err(cache_error(&cache_home))?;
+        let metadata = file.metadata().map_err(cache_error(file.path()))?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let name = file.file_name().to_string_lossy().into_owned();
+        let old = older_than.is_some_and(|older_than| {
+            metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > older_than)
+        });
+        let stem = CacheCompression::ALL
+            .iter()
+            .find_map(|compression| {
+                name.strip_suffix(compression.extension())
+                    .filter(|_| *compression != CacheCompression::None)
+            })
+            .unwrap_or(&name);
+        if old || !keep.contains(stem) {
+            fs::remove_file(file.path()).map_err(cache_error(file.path()))?;
+            removed += 1;
+            freed += metadata.len();
+        }
+    }
+    Ok((removed, freed))
+}
+
+/// Read the feed at `url` from the cache, without accessing the network,
+/// following the further pages that are cached as well.
+///
+/// Returns `None` if the feed has never been fetched.
+pub fn get_cached_feed_entries(url: &str, options: &FetchOptions) -> Result<Option<Feed>> {
+    let mut feed = match get_cached_feed_page(url, options.format)? {
+        Some(feed) => feed,
+        None => return Ok(None),
+    };
+    let mut visited = vec![url.to_string()];
+    while visited.len() <= options.next_pages && has_room(&feed, options) {
+        let next = match next_page(&feed, &visited) {
+            Some(next) => next,
+            None => break,
+        };
+        match get_cached_feed_page(&next, options.format) {
+            Ok(Some(page)) => append_page(&mut feed, page),
+            _ => break,
+        }
+        visited.push(next);
+    }
+    truncate_entries(&mut feed, options);
+    Ok(Some(feed))
+}
+
+fn get_cached_feed_page(url: &str, format: Option<FeedFormat>) -> Result<Option<Feed>> {
+    if let Some(path) = local_feed_path(url) {
+        return read_local_feed(url, &path, format).map(Some);
+    }
+    let digest = cache_digest(url);
+    let xdg_dirs = base_directories()?;
+    find_cache_file(&xdg_dirs, digest)
+        .map(|cache| read_cache_file(url, &cache, format))
+        .transpose()
+}
+
+/// The parts of an http response that fetching a feed looks at.
+struct Response {
+    status: reqwest::StatusCode,
+    last_modified: Option<DateTime<FixedOffset>>,
+    content_type: Option<String>,
+    /// Where the response redirects to.
+    location: Option<String>,
+    body: Vec<u8>,
+}
+
+/// The client for `get_feed_entries`. It follows redirects itself, so that it
+/// can tell when a feed has moved for good, and spaces out the requests to each
+/// host before sending at most `max_concurrent` requests at once.
+#[derive(Clone)]
+pub struct FeedClient {
+    client: reqwest::Client,
+    throttle: HostThrottle,
+    slots: Arc<tokio::sync::Semaphore>,
+}
+
+impl FeedClient {
+    pub fn new(max_concurrent: usize) -> FeedClient {
+        FeedClient {
+            client: reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("the http client should build"),
+            throttle: HostThrottle::default(),
+            slots: Arc::new(tokio::sync::Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Send `request` once it is polite to, holding a slot until the body of
+    /// the response is read.
+    async fn send(
+        &self,
+        url: &str,
+        request: reqwest::RequestBuilder,
+        body: bool,
+    ) -> Result<Response> {
+        // The wait comes first, so that requests waiting for their host don't
+        // hold up requests to other hosts.
+        self.throttle.wait(url).await;
+        let _slot = self
+            .slots
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+        Response::read(request.send().await?, body).await
+    }
+}
+
+/// How feeds are requested, so that fetching can be tested without a network.
+trait Transport {
+    async fn head(&self, url: &str, headers: &reqwest::header::HeaderMap) -> Result<Response>;
+    async fn get(&self, url: &str, headers: &reqwest::header::HeaderMap) -> Result<Response>;
+}
+
+impl Response {
+    async fn read(response: reqwest::Response, body: bool) -> Result<Response> {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .map(String::from)
+        };
+        let last_modified = header(reqwest::header::LAST_MODIFIED)
+            .and_then(|x| DateTime::parse_from_rfc2822(&x).ok());
+        let content_type = header(reqwest::header::CONTENT_TYPE);
+        Ok(Response {
+            status: response.status(),
+            last_modified,
+            content_type,
+            location: header(reqwest::header::LOCATION),
+            body: if body {
+                response.bytes().await?.to_vec()
+            } else {
+                vec![]
+            },
+        })
+    }
+}
+
+impl Transport for FeedClient {
+    async fn head(&self, url: &str, headers: &reqwest::header::HeaderMap) -> Result<Response> {
+        let request = self.client.head(url).headers(headers.clone());
+        self.send(url, request, false).await
+    }
+
+    async fn get(&self, url: &str, headers: &reqwest::header::HeaderMap) -> Result<Response> {
+        let request = self.client.get(url).headers(headers.clone());
+        self.send(url, request, true).await
+    }
+}
+
+/// Where fetched feeds are cached, by the digest of their url, so that
+/// fetching can be tested without touching the cache directory.
+trait CacheStore {
+    /// When the feed was last written to the cache, if it is cached.
+    fn modified(&self, digest: md5::Digest) -> Result<Option<SystemTime>>;
+    /// The uncompressed body of the cached feed.
+    fn read(&self, digest: md5::Digest) -> Result<Option<Vec<u8>>>;
+    fn write(
+        &self,
+        digest: md5::Digest,
+        content: &[u8],
+        compression: CacheCompression,
+    ) -> Result<()>;
+}
+
+/// The cache in the XDG cache directory.
+struct XdgCache;
+
+impl CacheStore for XdgCache {
+    fn modified(&self, digest: md5::Digest) -> Result<Option<SystemTime>> {
+        let xdg_dirs = base_directories()?;
+        Ok(find_cache_file(&xdg_dirs, digest)
+            .and_then(|cache| metadata(cache).and_then(|m| m.modified()).ok()))
+    }
+
+    fn read(&self, digest: md5::Digest) -> Result<Option<Vec<u8>>> {
+        let xdg_dirs = base_directories()?;
+        find_cache_file(&xdg_dirs, digest)
+            .map(|cache| read_cache_bytes(&cache))
+            .transpose()
+    }
+
+    fn write(
+        &self,
+        digest: md5::Digest,
+        content: &[u8],
+        compression: CacheCompression,
+    ) -> Result<()> {
+        let xdg_dirs = base_directories()?;
+        let path = xdg_dirs
+            .place_cache_file(compression.file_name(digest))
+            .map_err(cache_error(xdg_dirs.get_cache_home()))?;
+        write_cache_file(&path, content, compression)?;
+        for other in CacheCompression::ALL.iter() {
+            if *other != compression {
+                if let Some(stale) = xdg_dirs.find_cache_file(other.file_name(digest)) {
+                    // Another fetch of the same feed may have removed it already.
+                    match fs::remove_file(&stale) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                            return Err(cache_error(stale)(e))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fetch the feed at `url`, using the cached copy if it is still fresh. Local
+/// feeds are read directly, without caching. Further pages of paginated feeds
+/// are fetched, and cached, the same way, up to the limits in `options`.
+///
+/// Cached copies are read whether or not they are compressed; new copies are
+/// written using the compression in `options`. Fails with `Error::TimedOut` when the
+/// whole fetch takes longer than the timeout in `options`.
+///
+/// Errors are wrapped in `Error::Fetch`, which tells how long the fetch took.
+pub async fn get_feed_entries(
+    client: &FeedClient,
+    url: &str,
+    headers: &reqwest::header::HeaderMap,
+    options: &FetchOptions,
+) -> Result<Feed> {
+    let pages = get_feed_pages(client, &XdgCache, url, headers, options);
+    timed(url, options.timeout, pages).await
+}
+
+async fn timed(
+    url: &str,
+    timeout: Option<Duration>,
+    fetch: impl std::future::Future<Output = Result<Feed>>,
+) -> Result<Feed> {
+    let start = Instant::now();
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, fetch)
+            .await
+            .unwrap_or(Err(Error::TimedOut(timeout))),
+        None => fetch.await,
+    };
+    result.map_err(|e| Error::Fetch {
+        url: url.to_string(),
+        elapsed: start.elapsed(),
+        source: Box::new(e),
+    })
+}
+
+async fn get_feed_pages(
+    transport: &impl Transport,
+    cache: &impl CacheStore,
+    url: &str,
+    headers: &reqwest::header::HeaderMap,
+    options: &FetchOptions,
+) -> Result<Feed> {
+    let mut feed = get_feed_page(transport, cache, url, headers, options).await?;
+    let mut visited = vec![url.to_string()];
+    while visited.len() <= options.next_pages && has_room(&feed, options) {
+        let next = match next_page(&feed, &visited) {
+            Some(next) => next,
+            None => break,
+        };
+        match get_feed_page(transport, cache, &next, headers, options).await {
+            Ok(page) => append_page(&mut feed, page),
+            Err(e) => {
+                feed.warnings
+                    .push(format!("Couldn't fetch the next page {}: {}", next, e));
+                break;
+            }
+        }
+        visited.push(next);
+    }
+    truncate_entries(&mut feed, options);
+    Ok(feed)
+}
+
+async fn get_feed_page(
+    transport: &impl Transport,
+    cache: &impl CacheStore,
+    url: &str,
+    headers: &reqwest::header::HeaderMap,
+    options: &FetchOptions,
+) -> Result<Feed> {
+    if let Some(path) = local_feed_path(url) {
+        return read_local_feed(url, &path, options.format);
+    }
+    let digest = cache_digest(url);
+    let file_last_modified = cache.modified(digest)?;
+    let age = file_last_modified.and_then(|modified| modified.elapsed().ok());
+    let expired = age
+        .zip(options.max_age)
+        .is_some_and(|(age, max_age)| age >= max_age);
+    let cached = || -> Result<Option<Feed>> {
+        cache
+            .read(digest)?
+            .map(|content| read_feed_with_hint(url, &content[..], options.format))
+            .transpose()
+    };
+    if file_last_modified.is_some() {
+        if let Ok(Some(feed)) = cached() {
+            let recent = age
+                .zip(options.min_refresh)
+                .is_some_and(|(age, min_refresh)| age < min_refresh);
+            let fresh = feed
+                .update_interval
+                .zip(age)
+                .is_some_and(|(interval, age)| age < interval);
+            if recent || (fresh && !expired) {
+                return Ok(feed);
+            }
+        }
+    }
+    let (response, moved_to) = follow_redirects(transport, url, headers, false).await?;
+    if let (Some(file_last_modified), Some(url_last_modified)) =
+        (file_last_modified, response.last_modified)
+    {
+        if !expired && file_last_modified >= SystemTime::from(url_last_modified) {
+            if let Ok(Some(feed)) = cached() {
+                return Ok(moved(feed, moved_to));
+            }
+        }
+    }
+
+    let (response, moved_to) = follow_redirects(transport, url, headers, true).await?;
+    if !response.status.is_success() {
+        return Err(Error::Status {
+            url: url.to_string(),
+            status: response.status,
+        });
+    }
+    let hint = options.format.or_else(|| {
+        response
+            .content_type
+            .as_deref()
+            .and_then(FeedFormat::from_content_type)
+    });
+    let feed = read_feed_with_hint(url, &response.body[..], hint);
+    cache.write(digest, &response.body[..], options.compression)?;
+    if let Some(moved_to) = &moved_to {
+        // The feed is found in the cache once its url is changed to the new one.
+        cache.write(
+            cache_digest(moved_to),
+            &response.body[..],
+            options.compression,
+        )?;
+    }
+    feed.map(|feed| moved(feed, moved_to))
+}
+
+fn moved(feed: Feed, moved_to: Option<String>) -> Feed {
+    Feed { moved_to, ..feed }
+}
+
+/// Send a GET, or a HEAD unless `body`, to `url`, following redirects to http
+/// and https urls. Also returns the url that was redirected to in the end, if
+/// every redirect was permanent.
+///
+/// Redirects from https to http are refused, and credentials in `headers` are
+/// only sent on to the scheme, host and port of `url`.
+async fn follow_redirects(
+    transport: &impl Transport,
+    url: &str,
+    headers: &reqwest::header::HeaderMap,
+    body: bool,
+) -> Result<(Response, Option<String>)> {
+    let mut current = url.to_string();
+    let mut headers = headers.clone();
+    let mut permanent = true;
+    for _ in 0..=MAX_REDIRECTS {
+        let response = if body {
+            transport.get(&current, &headers).await?
+        } else {
+            transport.head(&current, &headers).await?
+        };
+        let next = response
+            .location
+            .as_deref()
+            .filter(|_| response.status.is_redirection())
+            .and_then(|location| resolve_link(&current, location))
+            .filter(|next| next.starts_with("http://") || next.starts_with("https://"));
+        match next {
+            Some(next) => {
+                if current.starts_with("https://") && next.starts_with("http://") {
+                    return Err(Error::InsecureRedirect {
+                        url: url.to_string(),
+                        to: next,
+                    });
+                }
+                if origin(&next) != origin(&current) {
+                    for name in &[
+                        reqwest::header::AUTHORIZATION,
+                        reqwest::header::COOKIE,
+                        reqwest::header::PROXY_AUTHORIZATION,
+                    ] {
+                        headers.remove(name);
+                    }
+                }
+                permanent &= matches!(
+                    response.status,
+                    reqwest::StatusCode::MOVED_PERMANENTLY
+                        | reqwest::StatusCode::PERMANENT_REDIRECT
+                );
+                current = next;
+            }
+            None => {
+                let moved_to = Some(current).filter(|current| permanent && current != url);
+                return Ok((response, moved_to));
+            }
+        }
+    }
+    Err(Error::TooManyRedirects {
+        url: url.to_string(),
+    })
+}
+
+/// The scheme, host and port of `url`.
+fn origin(url: &str) -> Option<(String, String, Option<u16>)> {
+    let url = reqwest::Url::parse(url).ok()?;
+    Some((
+        url.scheme().to_string(),
+        url.host_str()?.to_string(),
+        url.port_or_known_default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const URL: &str = "https://example.com/feed.xml";
+    const ATOM: &[u8] = include_bytes!("../tests/fixtures/atom.xml");
+    const RSS: &[u8] = include_bytes!("../tests/fixtures/rss.xml");
+
+    /// Answers every request with the same response, unless `redirects` has
+    /// a redirect for the url, and remembers the requests.
+    struct FakeTransport {
+        status: reqwest::StatusCode,
+        last_modified: Option<SystemTime>,
+        body: &'static [u8],
+        redirects: Vec<(&'static str, reqwest::StatusCode, &'static str)>,
+        requests: RefCell<Vec<&'static str>>,
+        /// The headers sent with each request.
+        headers: RefCell<Vec<reqwest::header::HeaderMap>>,
+    }
+
+    impl FakeTransport {
+        fn new(last_modified: Option<SystemTime>, body: &'static [u8]) -> FakeTransport {
+            FakeTransport {
+                status: reqwest::StatusCode::OK,
+                last_modified,
+                body,
+                redirects: vec![],
+                requests: RefCell::new(vec![]),
+                headers: RefCell::new(vec![]),
+            }
+        }
+
+        fn response(&self, url: &str, body: bool) -> Response {
+            if let Some((_, status, to)) = self.redirects.iter().find(|(from, _, _)| *from == url) {
+                return Response {
+                    status: *status,
+                    last_modified: None,
+                    content_type: None,
+                    location: Some(to.to_string()),
+                    body: vec![],
+                };
+            }
+            Response {
+                status: self.status,
+                last_modified: self
+                    .last_modified
+                    .map(|date| DateTime::<Utc>::from(date).into()),
+                content_type: None,
+                location: None,
+                body: if body { self.body.to_vec() } else { vec![] },
+            }
+        }
+    }
+
+    impl Transport for FakeTransport {
+        async fn head(&self, url: &str, headers: &reqwest::header::HeaderMap) -> Result<Response> {
+            self.requests.borrow_mut().push("HEAD");
+            self.headers.borrow_mut().push(headers.clone());
+            Ok(self.response(url, false))
+        }
+
+        async fn get(&self, url: &str, headers: &reqwest::header::HeaderMap) -> Result<Response> {
+            self.requests.borrow_mut().push("GET");
+            self.headers.borrow_mut().push(headers.clone());
+            Ok(self.response(url, true))
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeCache {
+        files: RefCell<HashMap<String, (Vec<u8>, SystemTime)>>,
+    }
+
+    impl FakeCache {
+        fn with(content: &[u8], modified: SystemTime) -> FakeCache {
+            let cache = FakeCache::default();
+            cache.files.borrow_mut().insert(
+                format!("{:x}", cache_digest(URL)),
+                (content.to_vec(), modified),
+            );
+            cache
+        }
+    }
+
+    impl CacheStore for FakeCache {
+        fn modified(&self, digest: md5::Digest) -> Result<Option<SystemTime>> {
+            let files = self.files.borrow();
+            Ok(files
+                .get(&format!("{:x}", digest))
+                .map(|(_, modified)| *modified))
+        }
+
+        fn read(&self, digest: md5::Digest) -> Result<Option<Vec<u8>>> {
+            let files = self.files.borrow();
+            Ok(files
+                .get(&format!("{:x}", digest))
+                .map(|(content, _)| content.clone()))
+        }
+
+        fn write(&self, digest: md5::Digest, content: &[u8], _: CacheCompression) -> Result<()> {
+            self.files.borrow_mut().insert(
+                format!("{:x}", digest),
+                (content.to_vec(), SystemTime::now()),
+            );
+            Ok(())
+        }
+    }
+
+    fn ago(seconds: u64) -> SystemTime {
+        SystemTime::now() - Duration::from_secs(seconds)
+    }
+
+    async fn fetch(
+        transport: &FakeTransport,
+        cache: &FakeCache,
+        options: FetchOptions,
+    ) -> Result<Feed> {
+        get_feed_page(transport, cache, URL, &Default::default(), &options).await
+    }
+
+    #[tokio::test]
+    async fn unmodified_feed_read_from_cache() {
+        let transport = FakeTransport::new(Some(ago(3600)), RSS);
+        let cache = FakeCache::with(ATOM, ago(60));
+        let feed = fetch(&transport, &cache, FetchOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(feed.title, "Example Atom Feed");
+        assert_eq!(*transport.requests.borrow(), ["HEAD"]);
+    }
+
+    #[tokio::test]
+    async fn modified_feed_fetched() {
+        let transport = FakeTransport::new(Some(ago(60)), RSS);
+        let cache = FakeCache::with(ATOM, ago(3600));
+        let feed = fetch(&transport, &cache, FetchOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(feed.title, "Example RSS Feed");
+        assert_eq!(*transport.requests.borrow(), ["HEAD", "GET"]);
+        assert_eq!(cache.read(cache_digest(URL)).unwrap().unwrap(), RSS);
+    }
+
+    #[tokio::test]
+    async fn feed_without_last_modified_fetched() {
+        let transport = FakeTransport::new(None, RSS);
+        let cache = FakeCache::with(ATOM, ago(60));
+        let feed = fetch(&transport, &cache, FetchOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(feed.title, "Example RSS Feed");
+        assert_eq!(*transport.requests.borrow(), ["HEAD", "GET"]);
+    }
+
+    #[tokio::test]
+    async fn recent_cache_used_without_requests() {
+        let transport = FakeTransport::new(Some(ago(0)), RSS);
+        let cache = FakeCache::with(ATOM, ago(60));
+        let options = FetchOptions {
+            min_refresh: Some(Duration::from_secs(600)),
+            ..FetchOptions::default()
+        };
+        let feed = fetch(&transport, &cache, options).await.unwrap();
+        assert_eq!(feed.title, "Example Atom Feed");
+        assert!(transport.requests.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_cache_fetched_again() {
+        let transport = FakeTransport::new(Some(ago(7200)), RSS);
+        let cache = FakeCache::with(ATOM, ago(3600));
+        let options = FetchOptions {
+            max_age: Some(Duration::from_secs(600)),
+            ..FetchOptions::default()
+        };
+        let feed = fetch(&transport, &cache, options).await.unwrap();
+        assert_eq!(feed.title, "Example RSS Feed");
+        assert_eq!(*transport.requests.borrow(), ["HEAD", "GET"]);
+    }
+
+    #[tokio::test]
+    async fn error_status_not_cached() {
+        let transport = FakeTransport {
+            status: reqwest::StatusCode::NOT_FOUND,
+            ..FakeTransport::new(None, b"Not Found")
+        };
+        let cache = FakeCache::default();
+        let error = fetch(&transport, &cache, FetchOptions::default())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::Status {
+                status: reqwest::StatusCode::NOT_FOUND,
+                ..
+            }
+        ));
+        assert!(cache.files.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn permanent_redirect_moves_feed() {
+        const HTTP_URL: &str = "http://example.com/feed.xml";
+        let transport = FakeTransport {
+            redirects: vec![(HTTP_URL, reqwest::StatusCode::MOVED_PERMANENTLY, URL)],
+            ..FakeTransport::new(None, RSS)
+        };
+        let cache = FakeCache::default();
+        let options = FetchOptions::default();
+        let feed = get_feed_page(&transport, &cache, HTTP_URL, &Default::default(), &options)
+            .await
+            .unwrap();
+        assert_eq!(feed.url, HTTP_URL);
+        assert_eq!(feed.moved_to.as_deref(), Some(URL));
+        assert!(feed.warnings.is_empty());
+        assert_eq!(*transport.requests.borrow(), ["HEAD", "HEAD", "GET", "GET"]);
+        assert_eq!(cache.read(cache_digest(HTTP_URL)).unwrap().unwrap(), RSS);
+        assert_eq!(cache.read(cache_digest(URL)).unwrap().unwrap(), RSS);
+
+        // A temporary redirect on the way means the feed may come back.
+        let transport = FakeTransport {
+            redirects: vec![
+                (
+                    HTTP_URL,
+                    reqwest::StatusCode::MOVED_PERMANENTLY,
+                    "/moved.xml",
+                ),
+                (
+                    "http://example.com/moved.xml",
+                    reqwest::StatusCode::FOUND,
+                    URL,
+                ),
+            ],
+            ..FakeTransport::new(None, RSS)
+        };
+        let feed = get_feed_page(&transport, &cache, HTTP_URL, &Default::default(), &options)
+            .await
+            .unwrap();
+        assert_eq!(feed.moved_to, None);
+        assert_eq!(feed.title, "Example RSS Feed");
+    }
+
+    #[tokio::test]
+    async fn cross_host_redirect_drops_credentials() {
+        const OTHER_URL: &str = "https://other.example.com/feed.xml";
+        let transport = FakeTransport {
+            redirects: vec![(URL, reqwest::StatusCode::FOUND, OTHER_URL)],
+            ..FakeTransport::new(None, RSS)
+        };
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            "Bearer secret".parse().unwrap(),
+        );
+        headers.insert(reqwest::header::COOKIE, "session=secret".parse().unwrap());
+        headers.insert(
+            reqwest::header::ACCEPT,
+            "application/rss+xml".parse().unwrap(),
+        );
+        let (response, _) = follow_redirects(&transport, URL, &headers, true)
+            .await
+            .unwrap();
+        assert_eq!(response.body, RSS);
+        let sent = transport.headers.borrow();
+        assert_eq!(sent[0], headers);
+        assert!(!sent[1].contains_key(reqwest::header::AUTHORIZATION));
+        assert!(!sent[1].contains_key(reqwest::header::COOKIE));
+        assert_eq!(sent[1][reqwest::header::ACCEPT], "application/rss+xml");
+    }
+
+    #[tokio::test]
+    async fn https_to_http_redirect_refused() {
+        let transport = FakeTransport {
+            redirects: vec![(
+                URL,
+                reqwest::StatusCode::MOVED_PERMANENTLY,
+                "http://example.com/feed.xml",
+            )],
+            ..FakeTransport::new(None, RSS)
+        };
+        let error = follow_redirects(&transport, URL, &Default::default(), true)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::InsecureRedirect { .. }));
+        assert_eq!(transport.requests.borrow().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_errors_timed() {
+        let transport = FakeTransport {
+            status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            ..FakeTransport::new(None, b"Down")
+        };
+        let cache = FakeCache::default();
+        let options = FetchOptions::default();
+        let error = timed(URL, None, fetch(&transport, &cache, options))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::Fetch { ref url, .. } if url == URL));
+        assert_eq!(
+            error.status(),
+            Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert!(!error.is_timeout());
+        assert!(error.elapsed().is_some());
+
+        let timeout = Duration::from_millis(10);
+        let error = timed(URL, Some(timeout), std::future::pending())
+            .await
+            .err()
+            .unwrap();
+        assert!(error.is_timeout());
+        assert_eq!(error.status(), None);
+        assert!(error.elapsed().unwrap() >= timeout);
+    }
+
+    #[test]
+    fn concurrent_cache_writes() {
+        let dir = std::env::temp_dir().join(format!("prss-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("feed");
+
+        let writers = (0..8u8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let content = vec![b'a' + i; 256 * 1024];
+                    for _ in 0..10 {
+                        write_cache_file(&path, &content, CacheCompression::None).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let content = fs::read(&path).unwrap();
+        assert_eq!(content.len(), 256 * 1024);
+        assert!(content.iter().all(|&b| b == content[0]));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compressed_cache_files() {
+        let dir = std::env::temp_dir().join(format!("prss-compressed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let content = b"<rss version=\"2.0\"></rss>".repeat(100);
+        for compression in CacheCompression::ALL.iter() {
+            let path = dir.join(compression.file_name(md5::compute("feed")));
+            write_cache_file(&path, &content, *compression).unwrap();
+            assert_eq!(read_cache_bytes(&path).unwrap(), content);
+            if *compression != CacheCompression::None {
+                assert!(fs::metadata(&path).unwrap().len() < content.len() as u64);
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
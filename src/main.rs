@@ -1,268 +1,2220 @@
-use std::fs::{metadata, File};
+mod command;
+mod config;
+mod digest;
+mod events;
+mod feedlist;
+mod json;
+mod template;
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
 use std::io;
-use std::io::{BufRead, BufReader, Read, Write};
-use std::process::Command;
+use std::io::Stdout;
+use std::io::{BufRead, BufReader, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail, Context, Result};
-use atom_syndication as atom;
-use chrono::{DateTime, Utc};
-use futures::StreamExt;
+use chrono::{DateTime, Local, Utc};
+use futures::{Future, FutureExt, StreamExt};
 use itertools::process_results;
+use prss::{
+    base_directories, cached_feed_path, get_cached_feed_body, get_cached_feed_entries,
+    get_feed_entries, normalize_url, purge_cache, Feed, FeedClient, FeedFormat, FetchOptions,
+};
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use structopt::StructOpt;
 use termion::event::Key;
-use termion::input::TermRead;
-use termion::raw::IntoRawMode;
-use termion::screen::AlternateScreen;
-use tui::backend::TermionBackend;
-use tui::layout::Margin;
-use tui::style::{Color, Style};
-use tui::widgets::{Block, Borders, List, ListItem, ListState};
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::{AlternateScreen, ToAlternateScreen, ToMainScreen};
+use tui::backend::{Backend, TermionBackend};
+use tui::layout::{Constraint, Direction, Layout, Margin, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
 use tui::Terminal;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-#[derive(Clone)]
-struct FeedEntry {
-    title: String,
-    url: String,
-    date: DateTime<Utc>,
+use crate::command::{Command, SortBy};
+use crate::config::{Config, FeedOrder, SortKey, SortOrder};
+use crate::digest::DigestFormat;
+use crate::events::{Event, Events};
+use crate::feedlist::{FeedInfo, FeedList, FeedListEntry, Row, View};
+use crate::template::{EntryFormat, Field, Part};
+
+const FEEDS_TXT_EXAMPLE: &str = "\
+# Add one feed url on each line. Lines starting with # are ignored.
+# https://blog.rust-lang.org/feed.xml
+";
+
+const FEED_COLORS: [Color; 12] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+];
+
+/// The most errors shown at once below the list of entries.
+const MAX_ERROR_LINES: usize = 5;
+
+/// Shown before entries whose reminder has passed.
+const REMINDER_MARKER: &str = "! ";
+
+/// How often the screen is drawn again while waiting for a key.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The pause between opening several entries, so that the browser opens
+/// their tabs in order.
+const BATCH_OPEN_INTERVAL: Duration = Duration::from_millis(300);
+
+/// How long `l` puts off an entry for.
+const DEFAULT_REMINDER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A simple rss reader in the terminal.
+///
+/// Feeds are read from ~/.config/prss/feeds.txt, one url on each line.
+#[derive(StructOpt)]
+struct Opt {
+    /// Only show feeds from the cache, without accessing the network
+    #[structopt(long)]
+    offline: bool,
+
+    /// Read the list of feeds from this file or http(s) url instead of feeds.txt
+    #[structopt(long, value_name = "FILE|URL")]
+    feeds: Option<String>,
+
+    /// Print the unread entries to stdout instead of starting the interface
+    #[structopt(long)]
+    list: bool,
+
+    /// Print the unread entries to stdout as JSON instead of starting the interface
+    #[structopt(long)]
+    json: bool,
+
+    /// Print a digest of the unread entries to stdout instead of starting the interface
+    #[structopt(long, value_name = "FORMAT", possible_values = &["html", "text"])]
+    digest: Option<DigestFormat>,
+
+    /// Send the digest by email with the digest_command in prss.toml instead of printing it
+    #[structopt(long, requires = "digest")]
+    send: bool,
+
+    /// Also print read entries with --list, --json or --digest
+    #[structopt(long)]
+    all: bool,
+
+    /// Don't print progress to stderr while fetching feeds with --list, --json or --digest,
+    /// nor a summary to stdout when quitting the interface
+    #[structopt(short, long)]
+    quiet: bool,
+
+    /// Fetch the feeds one at a time with --list, --json or --digest, printing each url to
+    /// stderr before fetching it, to find the feed that makes prss hang or crash
+    #[structopt(long)]
+    sequential: bool,
+
+    /// Only print entries newer than this time with --list, --json or --digest, given as a RFC 3339
+    /// date or as a duration ago such as 12h, 3d or 1w
+    #[structopt(long, value_name = "TIME|DURATION", parse(try_from_str = parse_since))]
+    since: Option<DateTime<Utc>>,
+
+    /// Mark the entries in this file read and exit. The file is either a list of
+    /// entry urls, one on each line, or a JSON export with read flags
+    #[structopt(long, value_name = "FILE")]
+    import_read: Option<PathBuf>,
+
+    /// Remove the cached copies of feeds that are no longer in the list of feeds and exit
+    #[structopt(long)]
+    purge_cache: bool,
+
+    /// Also remove cached copies older than this duration, such as 30d, with --purge-cache
+    #[structopt(long, value_name = "DURATION", parse(try_from_str = parse_duration))]
+    older_than: Option<Duration>,
 }
 
-struct Feed {
-    title: String,
-    entries: Vec<FeedEntry>,
+fn parse_since(since: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(since) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    let duration =
+        parse_duration(since).with_context(|| format!("Invalid time or duration: {}", since))?;
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| Utc::now().checked_sub_signed(duration))
+        .with_context(|| format!("Duration too long: {}", since))
 }
 
-impl Feed {
-    fn list_entries(&self) -> Vec<FeedListEntry> {
-        self.entries
-            .iter()
-            .map(|e| FeedListEntry {
-                title: format!("{} ({})", e.title, self.title),
-                url: e.url.clone(),
-                date: e.date,
-            })
-            .collect()
+/// The time `after` from now, unless that is too far off to represent.
+fn from_now(after: Duration) -> Option<DateTime<Utc>> {
+    chrono::Duration::from_std(after)
+        .ok()
+        .and_then(|after| Utc::now().checked_add_signed(after))
+}
+
+/// Parse a duration such as 30s, 12h or 1w.
+fn parse_duration(duration: &str) -> Result<Duration> {
+    let (amount, unit) = duration.split_at(
+        duration
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(duration.len()),
+    );
+    let amount: u64 = amount
+        .parse()
+        .with_context(|| format!("Invalid duration: {}", duration))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => bail!(
+            "Invalid duration unit in {}, expected s, m, h, d or w",
+            duration
+        ),
+    };
+    let seconds = amount
+        .checked_mul(seconds)
+        .with_context(|| format!("Duration too long: {}", duration))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+fn parse_feed_urls<I: Iterator<Item = String>>(lines: I) -> Vec<String> {
+    lines
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+fn read_feed_urls(path: &Path) -> Result<Vec<String>> {
+    let feeds_txt = File::open(path).with_context(|| format!("{}", path.display()))?;
+    Ok(process_results(
+        BufReader::new(feeds_txt).lines(),
+        |lines| parse_feed_urls(lines),
+    )?)
+}
+
+/// The settings of a feed given after its url in feeds.txt.
+#[derive(Clone, Default)]
+struct FeedSettings {
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    /// Whether the feed is only read from the cache, without fetching it.
+    disabled: bool,
+    format: Option<FeedFormat>,
+}
+
+impl FeedSettings {
+    fn fetch_options(&self, defaults: &FetchOptions) -> FetchOptions {
+        FetchOptions {
+            timeout: self.timeout.or(defaults.timeout),
+            format: self.format.or(defaults.format),
+            ..*defaults
+        }
     }
 }
 
-#[derive(Clone)]
-struct FeedListEntry {
-    title: String,
-    url: String,
-    date: DateTime<Utc>,
+/// Split a line of feeds.txt into the feed url and the settings given after
+/// it: http headers as `header:Name=Value`, a fetch timeout as
+/// `timeout:<duration>`, the format to read the feed as first as
+/// `format:atom` or `format:rss`, and `disabled`. Header values are kept out
+/// of error messages.
+fn parse_feed_line(line: &str) -> Result<(String, FeedSettings)> {
+    let annotation = Regex::new(r" (header:|timeout:|format:|disabled\b)").unwrap();
+    let starts = annotation.find_iter(line).collect::<Vec<_>>();
+    let url = line[..starts.first().map_or(line.len(), |m| m.start())]
+        .trim()
+        .to_string();
+    let mut settings = FeedSettings::default();
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(line.len(), |m| m.start());
+        let value = &line[start.end()..end];
+        if start.as_str() == " timeout:" {
+            let timeout = parse_duration(value.trim())
+                .with_context(|| format!("Invalid timeout for {}", url))?;
+            settings.timeout = Some(timeout);
+            continue;
+        }
+        if start.as_str() == " format:" {
+            settings.format = Some(match value.trim() {
+                "atom" => FeedFormat::Atom,
+                "rss" => FeedFormat::Rss,
+                format => bail!(
+                    "Invalid format {} for {}, expected atom or rss",
+                    format,
+                    url
+                ),
+            });
+            continue;
+        }
+        if start.as_str() == " disabled" {
+            if !value.trim().is_empty() {
+                bail!("Unexpected {:?} after disabled for {}", value.trim(), url);
+            }
+            settings.disabled = true;
+            continue;
+        }
+        let (name, value) = value
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid header for {}, expected header:Name=Value", url))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .with_context(|| format!("Invalid header name for {}", url))?;
+        let mut value = HeaderValue::from_str(value.trim())
+            .with_context(|| format!("Invalid value of header {} for {}", name, url))?;
+        value.set_sensitive(true);
+        settings.headers.append(name, value);
+    }
+    Ok((url, settings))
 }
 
-struct FeedList {
-    items: Vec<FeedListEntry>,
-    state: ListState,
+type FeedSettingsMap = HashMap<String, FeedSettings>;
+
+/// The feeds that were fetched, and the errors of those that couldn't be.
+type Fetched = (Vec<Feed>, Vec<String>);
+
+fn parse_feed_lines(lines: &[String]) -> Result<(Vec<String>, FeedSettingsMap)> {
+    let mut urls = vec![];
+    let mut settings = HashMap::new();
+    for line in lines {
+        let (url, feed_settings) = parse_feed_line(line)?;
+        urls.push(url.clone());
+        settings.insert(url, feed_settings);
+    }
+    Ok((urls, settings))
 }
 
-impl FeedList {
-    fn new(items: Vec<Feed>) -> FeedList {
-        let mut items = items
+/// Rewrite feeds.txt with the feeds in the order of `urls`, leaving comments and
+/// blank lines where they are.
+fn persist_feed_order(path: &Path, urls: &[String]) -> Result<()> {
+    let content = fs::read_to_string(path).with_context(|| format!("{}", path.display()))?;
+    let lines = content.lines().map(String::from).collect::<Vec<_>>();
+    let listed = parse_feed_urls(lines.iter().cloned())
+        .into_iter()
+        .map(|line| Ok((parse_feed_line(&line)?.0, line)))
+        .collect::<Result<Vec<_>>>()?;
+    let line_of = |url: &String| listed.iter().find(|(u, _)| u == url).map(|(_, l)| l);
+    let mut ordered = urls.iter().filter_map(line_of).chain(
+        listed
             .iter()
-            .map(|e| e.list_entries())
-            .collect::<Vec<Vec<_>>>()
-            .concat();
+            .filter(|(url, _)| !urls.contains(url))
+            .map(|(_, line)| line),
+    );
 
-        items.sort_by_key(|x| x.date);
-        items.reverse();
-
-        let mut state = ListState::default();
-        if !items.is_empty() {
-            state.select(Some(0));
+    let mut f = File::create(path).with_context(|| format!("{}", path.display()))?;
+    for line in &lines {
+        if listed.iter().any(|(_, l)| l == line) {
+            writeln!(f, "{}", ordered.next().unwrap())?;
+        } else {
+            writeln!(f, "{}", line)?;
         }
+    }
+    Ok(())
+}
+
+fn is_remote(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
 
-        FeedList { items, state }
+async fn fetch_feed_list(url: &str) -> Result<String> {
+    Ok(reqwest::get(url).await?.error_for_status()?.text().await?)
+}
+
+async fn get_remote_feed_urls(url: &str, offline: bool) -> Result<Vec<String>> {
+    let xdg_dirs = base_directories()?;
+    let cache_name = format!("{:x}", md5::compute(url));
+    if !offline {
+        if let Ok(content) = fetch_feed_list(url).await {
+            let path = xdg_dirs.place_cache_file(&cache_name)?;
+            File::create(path)?.write_all(content.as_bytes())?;
+            return Ok(parse_feed_urls(content.lines().map(String::from)));
+        }
     }
+    let cache = xdg_dirs
+        .find_cache_file(&cache_name)
+        .ok_or_else(|| anyhow!("Couldn't fetch feed list from {}", url))?;
+    let content = std::fs::read_to_string(cache)?;
+    Ok(parse_feed_urls(content.lines().map(String::from)))
+}
 
-    pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
+/// Read urls, one on each line, along with how many lines were skipped for not
+/// being valid UTF-8, such as after an interrupted write. The skipped lines are
+/// gone from the file once it is written again.
+fn get_read_entries(path: &Path) -> Result<(HashSet<String>, usize)> {
+    if !path.exists() {
+        return Ok((HashSet::new(), 0));
+    }
+    let content = fs::read(path).with_context(|| path.display().to_string())?;
+    let mut urls = HashSet::new();
+    let mut skipped = 0;
+    for line in content.split(|&b| b == b'\n') {
+        match std::str::from_utf8(line) {
+            Ok(url) => {
+                let url = url.trim_end_matches('\r');
+                if !url.is_empty() {
+                    urls.insert(url.to_string());
                 }
             }
-            None => 0,
-        };
-        self.state.select(Some(i));
+            Err(_) => skipped += 1,
+        }
+    }
+    Ok((urls, skipped))
+}
+
+fn skipped_lines_warning(path: &Path, skipped: usize) -> Option<String> {
+    match skipped {
+        0 => None,
+        1 => Some(format!(
+            "Skipped a line of {} that isn't valid UTF-8",
+            path.display()
+        )),
+        n => Some(format!(
+            "Skipped {} lines of {} that aren't valid UTF-8",
+            n,
+            path.display()
+        )),
     }
+}
 
-    pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
+fn persist_read_entries(path: &Path, read: &HashSet<String>) -> Result<()> {
+    let mut urls = read.iter().collect::<Vec<_>>();
+    urls.sort();
+    let content = urls
+        .into_iter()
+        .map(|url| format!("{}\n", url))
+        .collect::<String>();
+    let mut f = File::create(path).with_context(|| path.display().to_string())?;
+    f.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Read the dates of entries, such as reminders, one `<RFC 3339 date> <url>`
+/// on each line.
+fn get_entry_dates(path: &Path) -> Result<HashMap<String, DateTime<Utc>>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = fs::read_to_string(path).with_context(|| format!("{}", path.display()))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let invalid = || format!("Invalid line in {}: {}", path.display(), line);
+            let (date, url) = line.split_once(' ').ok_or_else(|| anyhow!(invalid()))?;
+            let date = DateTime::parse_from_rfc3339(date).with_context(invalid)?;
+            Ok((url.to_string(), date.with_timezone(&Utc)))
+        })
+        .collect()
+}
+
+fn persist_entry_dates(path: &Path, dates: &HashMap<String, DateTime<Utc>>) -> Result<()> {
+    let mut dates = dates.iter().collect::<Vec<_>>();
+    dates.sort();
+    let content = dates
+        .into_iter()
+        .map(|(url, date)| format!("{} {}\n", date.to_rfc3339(), url))
+        .collect::<String>();
+    let mut f = File::create(path).with_context(|| format!("{}", path.display()))?;
+    f.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+fn get_sort_order(path: &Path) -> Result<Option<SortOrder>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).with_context(|| format!("{}", path.display()))?;
+    let order = toml::from_str(&content).with_context(|| format!("{}", path.display()))?;
+    Ok(Some(order))
+}
+
+fn persist_sort_order(path: &Path, order: SortOrder) -> Result<()> {
+    let content = toml::to_string(&order)?;
+    fs::write(path, content).with_context(|| format!("{}", path.display()))
+}
+
+/// Add the entry urls in `import`, a list of urls or a JSON export, to the read
+/// entries, returning how many of them weren't read already.
+fn import_read_entries(import: &Path, read_entries_path: &Path) -> Result<usize> {
+    let content = fs::read_to_string(import).with_context(|| format!("{}", import.display()))?;
+    let urls = if content.trim_start().starts_with(&['{', '['][..]) {
+        json::read_urls(&content).with_context(|| format!("{}", import.display()))?
+    } else {
+        parse_feed_urls(content.lines().map(|line| line.trim().to_string()))
+    };
+    let (mut read, skipped) = get_read_entries(read_entries_path)?;
+    if let Some(warning) = skipped_lines_warning(read_entries_path, skipped) {
+        eprintln!("{}", warning);
+    }
+    let before = read.len();
+    read.extend(urls);
+    persist_read_entries(read_entries_path, &read)?;
+    Ok(read.len() - before)
+}
+
+fn feed_color(title: &str) -> Color {
+    let digest = md5::compute(title);
+    FEED_COLORS[digest[0] as usize % FEED_COLORS.len()]
+}
+
+/// Fetch the feeds, returning them along with the errors of the feeds that
+/// couldn't be fetched or read from the cache.
+async fn fetch_feeds(
+    feed_urls: &[String],
+    offline: bool,
+    settings: &FeedSettingsMap,
+    cache: &FetchOptions,
+    max_concurrent: usize,
+    log_urls: bool,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<Fetched> {
+    let total = feed_urls.len();
+    let (cached, fetched): (Vec<_>, Vec<_>) = feed_urls
+        .iter()
+        .partition(|url| offline || settings.get(*url).is_some_and(|s| s.disabled));
+    let mut feeds = vec![];
+    let mut errors = vec![];
+    for (i, url) in cached.iter().enumerate() {
+        progress(i + 1, total);
+        if log_urls {
+            eprintln!("reading {} from the cache", url);
+        }
+        let settings = settings.get(*url);
+        let options = settings.map_or(*cache, |settings| settings.fetch_options(cache));
+        let disabled = settings.is_some_and(|settings| settings.disabled);
+        // Feeds that aren't cached are left out, as are disabled feeds whose
+        // cache is broken, which are often disabled for being broken.
+        match get_cached_feed_entries(url, &options) {
+            Ok(Some(feed)) => feeds.push(feed),
+            Ok(None) => {}
+            Err(_) if disabled => {}
+            Err(e) => errors.push(fetch_error_line(url, &e)),
+        }
+    }
+    if fetched.is_empty() {
+        return Ok((feeds, errors));
+    }
+
+    let client = FeedClient::new(max_concurrent);
+
+    // The fetches are collected before they are run, which keeps the future
+    // of this function `Send`, so that it can be spawned.
+    let fetches = fetched
+        .iter()
+        .map(|&url| {
+            let client = client.clone();
+            let settings = settings.get(url).cloned().unwrap_or_default();
+            let options = settings.fetch_options(cache);
+            async move {
+                if log_urls {
+                    eprintln!("fetching {}", url);
                 }
+                get_feed_entries(&client, url, &settings.headers, &options)
+                    .await
+                    .map_err(|e| fetch_error_line(url, &e))
             }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        })
+        .collect::<Vec<_>>();
+    let fetches = futures::stream::iter(fetches)
+        // All fetches run to completion, so a failing feed doesn't hold up the
+        // others. The client limits how many requests are sent at once, but
+        // when logging, a url is only printed once the fetch before it is done.
+        .buffer_unordered(if log_urls { 1 } else { fetched.len() })
+        .enumerate()
+        .map(|(i, feed)| {
+            progress(cached.len() + i + 1, total);
+            feed
+        })
+        .collect::<Vec<_>>()
+        .await;
+    for fetch in fetches {
+        match fetch {
+            Ok(feed) => feeds.push(feed),
+            Err(error) => errors.push(error),
+        }
     }
+    Ok((feeds, errors))
+}
 
-    pub fn get(&self) -> &FeedListEntry {
-        &self.items[self.state.selected().expect("impossible")]
+/// A line for the error pane about the feed at `url`, with the HTTP status
+/// and how long the fetch took, so that a feed that is down for a moment can
+/// be told apart from one that is gone.
+fn fetch_error_line(url: &str, error: &prss::Error) -> String {
+    let mut details = vec![];
+    if let Some(status) = error.status() {
+        details.push(format!("HTTP {}", status));
+    }
+    if error.is_timeout() {
+        details.push("timed out".to_string());
+    }
+    match error.elapsed() {
+        Some(elapsed) if elapsed < Duration::from_secs(1) => {
+            details.push(format!("{}ms", elapsed.as_millis()))
+        }
+        Some(elapsed) => details.push(format!("{:.1}s", elapsed.as_secs_f64())),
+        None => {}
+    }
+    if details.is_empty() {
+        format!("{}: {}", url, error)
+    } else {
+        format!("{} ({}): {}", url, details.join(", "), error)
     }
 }
 
-fn read_feed(url: &str, content: &[u8]) -> Result<Feed> {
-    if let Ok(feed) = atom::Feed::read_from(content) {
-        Ok(Feed {
-            title: feed.title().to_string(),
-            entries: feed
-                .entries
-                .into_iter()
-                .map(move |e| FeedEntry {
-                    title: e.title().to_string(),
-                    url: e.links().first().unwrap().href.clone(),
-                    date: DateTime::<Utc>::from(e.published.unwrap()),
-                })
-                .collect(),
+/// Fetch the feeds and sort them in the configured order of feeds.
+async fn fetch_sorted_feeds(
+    feed_urls: &[String],
+    offline: bool,
+    settings: &FeedSettingsMap,
+    config: &Config,
+) -> Result<Fetched> {
+    let (mut feeds, errors) = fetch_feeds(
+        feed_urls,
+        offline,
+        settings,
+        &config.fetch_options(),
+        config.max_concurrent_fetches,
+        false,
+        |_, _| {},
+    )
+    .await?;
+    sort_feeds(&mut feeds, feed_urls, config.feed_order);
+    Ok((feeds, errors))
+}
+
+/// Subscribe to `url` by adding it to the end of feeds.txt.
+fn add_feed(opt: &Opt, feeds_txt_path: &Path, feed_urls: &[String], url: &str) -> Result<()> {
+    if opt.feeds.as_deref().is_some_and(is_remote) {
+        bail!("Feeds can't be added to a list of feeds given as a url");
+    }
+    let normalized = normalize_url(url);
+    if feed_urls.iter().any(|u| normalize_url(u) == normalized) {
+        bail!("Already subscribed to {}", url);
+    }
+    let content =
+        fs::read(feeds_txt_path).with_context(|| format!("{}", feeds_txt_path.display()))?;
+    let mut f = fs::OpenOptions::new()
+        .append(true)
+        .open(feeds_txt_path)
+        .with_context(|| format!("{}", feeds_txt_path.display()))?;
+    // Otherwise the url would be glued to the last line.
+    if content.last().is_some_and(|c| *c != b'\n') {
+        writeln!(f)?;
+    }
+    writeln!(f, "{}", url)?;
+    Ok(())
+}
+
+/// Add or remove the `disabled` annotation of the feed `url` in feeds.txt.
+fn set_feed_disabled(opt: &Opt, feeds_txt_path: &Path, url: &str, disabled: bool) -> Result<()> {
+    let annotation = Regex::new(r" disabled\b").unwrap();
+    rewrite_feed_line(opt, feeds_txt_path, url, |line| {
+        let line = annotation.replace_all(line, "").into_owned();
+        if disabled {
+            format!("{} disabled", line)
+        } else {
+            line
+        }
+    })
+}
+
+/// Tell which feeds have moved for good. If `update`, their urls are changed
+/// in feeds.txt, telling what was changed or why it couldn't be instead.
+fn moved_feeds(opt: &Opt, feeds_txt_path: &Path, feeds: &[Feed], update: bool) -> Vec<String> {
+    feeds
+        .iter()
+        .filter_map(|feed| Some((&feed.url, feed.moved_to.as_ref()?)))
+        .map(|(url, moved_to)| {
+            if !update {
+                return format!("{} moved for good to {}", url, moved_to);
+            }
+            match rewrite_feed_line(opt, feeds_txt_path, url, |line| {
+                line.replacen(url.as_str(), moved_to, 1)
+            }) {
+                Ok(()) => format!("Moved {} to {} in feeds.txt", url, moved_to),
+                Err(e) => format!("Couldn't move {} to {}: {}", url, moved_to, e),
+            }
         })
-    } else if let Ok(channel) = rss::Channel::read_from(content) {
-        let t = channel.title.clone();
-        Ok(Feed {
-            title: channel.title.clone(),
-            entries: channel
-                .items
-                .into_iter()
-                .map(move |i| FeedEntry {
-                    title: i.title().unwrap_or("").to_string(),
-                    url: i.link().unwrap().to_string(),
-                    date: DateTime::<Utc>::from(
-                        i.pub_date
-                            .as_ref()
-                            .and_then(|d| {
-                                chrono::DateTime::parse_from_rfc2822(&d.replace("UTC", "+0000"))
-                                    .ok()
-                            })
-                            .unwrap_or_else(|| {
-                                panic!(
-                                    "title: {}, url: {}: couldn't parse i.pub_date {:?}",
-                                    t.clone(),
-                                    url,
-                                    i.pub_date.map(|x| x.replace("UTC", "GMT"))
-                                )
-                            }),
-                    ),
-                })
-                .collect(),
+        .collect()
+}
+
+/// Replace the line of the feed at `url` in feeds.txt by `rewrite` of it.
+fn rewrite_feed_line(
+    opt: &Opt,
+    feeds_txt_path: &Path,
+    url: &str,
+    rewrite: impl Fn(&str) -> String,
+) -> Result<()> {
+    if opt.feeds.as_deref().is_some_and(is_remote) {
+        bail!("Feeds can't be changed in a list of feeds given as a url");
+    }
+    let content = fs::read_to_string(feeds_txt_path)
+        .with_context(|| format!("{}", feeds_txt_path.display()))?;
+    let mut found = false;
+    let lines = content
+        .lines()
+        .map(|line| {
+            if line.trim().is_empty() || line.starts_with('#') || parse_feed_line(line)?.0 != url {
+                return Ok(line.to_string());
+            }
+            found = true;
+            Ok(rewrite(line))
         })
-    } else {
-        bail!("Couldn't read Atom or RSS from url: {}", url)
-    }
-}
-
-async fn get_feed_entries(client: &reqwest::Client, url: &str) -> Result<Feed> {
-    let digest = md5::compute(url);
-    let xdg_dirs = xdg::BaseDirectories::with_prefix("prss")?;
-    let cache_file = xdg_dirs.find_cache_file(format!("{:x}", digest));
-    let response = client.head(url).send().await?;
-    match (
-        cache_file
-            .ok_or_else(|| anyhow!("Cachefile not found"))
-            .and_then(|x| Ok((x.clone(), metadata(x).context("metadata")?)))
-            .and_then(|(y, x)| Ok((y, x.modified().context("modified")?))),
-        response
-            .headers()
-            .get(reqwest::header::LAST_MODIFIED)
-            .ok_or_else(|| anyhow!("No last_modified header found"))
-            .and_then(|x| x.to_str().context("to_str"))
-            .and_then(|x| DateTime::parse_from_rfc2822(x).context("parse_from_rfc2822")),
-    ) {
-        (Ok((cache, file_last_modified)), Ok(url_last_modified))
-            if file_last_modified >= std::convert::From::from(url_last_modified) =>
-        {
-            let mut handle = File::open(cache).context("open")?;
-            let mut buf = vec![];
-            handle.read_to_end(&mut buf)?;
-            read_feed(url, &buf[..])
-        }
-        _ => {
-            let content = reqwest::get(url).await?.bytes().await?;
-            let feed = read_feed(url, &content[..]);
-            let path = xdg_dirs.place_cache_file(format!("{:x}", digest))?;
-            let mut f = File::create(path)?;
-            f.write_all(&content[..])?;
-            feed
+        .collect::<Result<Vec<_>>>()?;
+    if !found {
+        bail!("{} is not in {}", url, feeds_txt_path.display());
+    }
+    fs::write(feeds_txt_path, lines.join("\n") + "\n")
+        .with_context(|| format!("{}", feeds_txt_path.display()))?;
+    Ok(())
+}
+
+fn print_list<'a>(entries: impl IntoIterator<Item = &'a FeedListEntry>) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for entry in entries {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            entry.date.to_rfc3339(),
+            entry.feed_title,
+            entry.title,
+            entry.url
+        )?;
+    }
+    Ok(())
+}
+
+/// Send a digest as an email by writing it to the stdin of the
+/// `digest_command`, such as `sendmail you@example.com`.
+fn send_digest(config: &Config, format: DigestFormat, title: &str, digest: &str) -> Result<()> {
+    let command = config
+        .digest_command
+        .as_deref()
+        .ok_or_else(|| anyhow!("Set digest_command in prss.toml to send digests"))?;
+    let mut child = process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", command))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    write!(
+        stdin,
+        "Subject: {}\nMIME-Version: 1.0\nContent-Type: {}\n\n{}",
+        title,
+        format.content_type(),
+        digest
+    )?;
+    drop(stdin);
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{} failed with {}", command, status);
+    }
+    Ok(())
+}
+
+fn sort_feeds(feeds: &mut [Feed], feed_urls: &[String], order: FeedOrder) {
+    match order {
+        FeedOrder::Recent => {}
+        FeedOrder::Listed => {
+            feeds.sort_by_key(|feed| feed_urls.iter().position(|url| url == &feed.url))
+        }
+        FeedOrder::Alphabetical => feeds.sort_by_key(|feed| feed.title.to_lowercase()),
+    }
+}
+
+fn elide_middle(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    // Widths are measured in terminal columns, so that wide characters such as
+    // CJK and emoji count twice.
+    let tail_width = (max_width - 1) / 2;
+    let head_width = max_width - 1 - tail_width;
+    let fitting = |chars: &mut dyn Iterator<Item = char>, max_width: usize| {
+        let mut width = 0;
+        chars
+            .take_while(|c| {
+                width += c.width().unwrap_or(0);
+                width <= max_width
+            })
+            .collect::<Vec<_>>()
+    };
+    let head = fitting(&mut text.chars(), head_width);
+    let mut tail = fitting(&mut text.chars().rev(), tail_width);
+    tail.reverse();
+    head.into_iter()
+        .chain(std::iter::once('…'))
+        .chain(tail)
+        .collect()
+}
+
+fn skipped_note(feed: &FeedInfo) -> String {
+    match feed.warnings.len() {
+        0 => String::new(),
+        n => format!(", {} skipped", n),
+    }
+}
+
+/// A feed row, followed by the description of the feed on its own line.
+fn feed_item(feed: &FeedInfo, indent: &str, line: Spans<'static>) -> ListItem<'static> {
+    let mut lines = vec![line];
+    if let Some(description) = &feed.description {
+        lines.push(Spans::from(Span::styled(
+            format!(
+                "{}{}",
+                indent,
+                description.split_whitespace().collect::<Vec<_>>().join(" ")
+            ),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    ListItem::new(lines)
+}
+
+/// An entry as laid out by `format`, with the already shortened `title`.
+fn entry_spans(
+    format: &EntryFormat,
+    entry: &FeedListEntry,
+    title: String,
+    style: Style,
+) -> Spans<'static> {
+    let now = Utc::now();
+    let mut title = Some(title);
+    Spans::from(
+        format
+            .0
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => Span::styled(text.clone(), style),
+                Part::Field(Field::Title) => {
+                    Span::styled(title.take().unwrap_or_else(|| entry.title.clone()), style)
+                }
+                Part::Field(Field::Feed) => Span::styled(
+                    entry.feed_title.clone(),
+                    Style::default().fg(feed_color(&entry.feed_title)),
+                ),
+                Part::Field(field) => Span::styled(field.value(entry, now), style),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn list_items(
+    feedlist: &FeedList,
+    max_title_width: Option<usize>,
+    entry_format: &EntryFormat,
+) -> Vec<ListItem<'static>> {
+    feedlist
+        .rows
+        .iter()
+        .map(|row| match *row {
+            Row::Date(i) => ListItem::new(Spans::from(Span::styled(
+                feedlist.date_heading(i),
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+            ))),
+            Row::Feed(i) if feedlist.view == View::Summary => {
+                let feed = &feedlist.feeds[i];
+                let newest = match feedlist.newest_date(feed) {
+                    Some(date) => date.format("%Y-%m-%d %H:%M").to_string(),
+                    None => "no entries".to_string(),
+                };
+                feed_item(
+                    feed,
+                    "",
+                    Spans::from(vec![
+                        Span::styled(
+                            feed.title.clone(),
+                            Style::default()
+                                .fg(feed_color(&feed.title))
+                                .add_modifier(Modifier::BOLD),
+                        ),
+                        Span::raw(format!(
+                            " ({} unread{}, newest {})",
+                            feedlist.unread_count(feed),
+                            skipped_note(feed),
+                            newest
+                        )),
+                    ]),
+                )
+            }
+            Row::Feed(i) => {
+                let feed = &feedlist.feeds[i];
+                let marker = if feedlist.is_expanded(feed) {
+                    "▾"
+                } else {
+                    "▸"
+                };
+                feed_item(
+                    feed,
+                    "  ",
+                    Spans::from(vec![
+                        Span::raw(format!("{} ", marker)),
+                        Span::styled(
+                            feed.title.clone(),
+                            Style::default()
+                                .fg(feed_color(&feed.title))
+                                .add_modifier(Modifier::BOLD),
+                        ),
+                        Span::raw(format!(
+                            " ({} unread{})",
+                            feedlist.unread_count(feed),
+                            skipped_note(feed)
+                        )),
+                    ]),
+                )
+            }
+            Row::Entry(i) => {
+                let entry = &feedlist.items[i];
+                let title = match max_title_width {
+                    Some(width) => elide_middle(&entry.title, width),
+                    None => entry.title.clone(),
+                };
+                let due = feedlist.is_due(entry);
+                let style = if due {
+                    Style::default()
+                        .fg(Color::Magenta)
+                        .add_modifier(Modifier::BOLD)
+                } else if feedlist.is_read(entry) {
+                    Style::default().fg(Color::DarkGray)
+                } else if feedlist.is_highlighted(entry) {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                match feedlist.view {
+                    View::Flat | View::Summary => {
+                        let mut spans = entry_spans(entry_format, entry, title, style);
+                        if due {
+                            spans.0.insert(0, Span::styled(REMINDER_MARKER, style));
+                        }
+                        ListItem::new(spans)
+                    }
+                    View::Grouped => {
+                        let marker = if due { REMINDER_MARKER } else { "" };
+                        ListItem::new(Spans::from(Span::styled(
+                            format!("  {}{}", marker, title),
+                            style,
+                        )))
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+fn preview_text(entry: &FeedListEntry) -> Vec<Spans<'static>> {
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            entry.title.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(match &entry.hub {
+            Some(hub) => format!("Feed: {} (WebSub hub: {})", entry.feed_title, hub),
+            None => format!("Feed: {}", entry.feed_title),
+        }),
+        Spans::from(format!(
+            "Author: {}",
+            entry.author.as_deref().unwrap_or("unknown")
+        )),
+        Spans::from(format!("Date: {}", entry.date.format("%Y-%m-%d %H:%M"))),
+        Spans::from(format!("Link: {}", entry.url)),
+    ];
+    if let Some(image) = &entry.image_url {
+        lines.push(Spans::from(format!("Image: {}", image)));
+    }
+    match (entry.comments, &entry.comments_url) {
+        (Some(count), Some(url)) => {
+            lines.push(Spans::from(format!("Comments: {} at {}", count, url)))
+        }
+        (Some(count), None) => lines.push(Spans::from(format!("Comments: {}", count))),
+        (None, Some(url)) => lines.push(Spans::from(format!("Comments: {}", url))),
+        (None, None) => {}
+    }
+    if !entry.categories.is_empty() {
+        lines.push(Spans::from(format!(
+            "Categories: {}",
+            entry.categories.join(", ")
+        )));
+    }
+    lines
+}
+
+/// A popup for choosing which of the links of an entry to open.
+struct LinkPicker {
+    links: Vec<String>,
+    state: ListState,
+}
+
+impl LinkPicker {
+    fn new(links: Vec<String>) -> LinkPicker {
+        let mut state = ListState::default();
+        state.select(Some(0));
+        LinkPicker { links, state }
+    }
+
+    fn selected(&self) -> &str {
+        &self.links[self.state.selected().unwrap_or(0)]
+    }
+
+    fn next(&mut self) {
+        let i = self.state.selected().unwrap_or(0);
+        self.state.select(Some((i + 1) % self.links.len()));
+    }
+
+    fn previous(&mut self) {
+        let i = self.state.selected().unwrap_or(0);
+        self.state
+            .select(Some((i + self.links.len() - 1) % self.links.len()));
+    }
+}
+
+/// A rectangle of at most `width` by `height` in the middle of `rect`.
+fn centered(rect: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(rect.width);
+    let height = height.min(rect.height);
+    Rect::new(
+        rect.x + (rect.width - width) / 2,
+        rect.y + (rect.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// The commands to try for a `browser` in the config, where `$NAME` stands for
+/// the commands in the environment variable, separated by colons.
+fn browser_commands(browser: &str) -> Vec<String> {
+    match browser.strip_prefix('$') {
+        Some(name) => std::env::var(name)
+            .unwrap_or_default()
+            .split(':')
+            .filter(|command| !command.trim().is_empty())
+            .map(String::from)
+            .collect(),
+        None => vec![browser.to_string()],
+    }
+}
+
+/// Open `url` with the first of `browsers` that runs and succeeds.
+fn open_in_browser(browsers: &[String], url: &str) -> Result<()> {
+    let mut failures = vec![];
+    for command in browsers
+        .iter()
+        .flat_map(|browser| browser_commands(browser))
+    {
+        match open_command(&command, url).status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => failures.push(format!("{} exited with {}", command, status)),
+            Err(e) => failures.push(format!("{}: {}", command, e)),
+        }
+    }
+    if failures.is_empty() {
+        bail!("Couldn't open {}: no browser is configured", url);
+    }
+    bail!("Couldn't open {} ({})", url, failures.join("; "))
+}
+
+fn copy_to_clipboard<W: Write>(out: &mut W, text: &str) -> io::Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", base64::encode(text))?;
+    out.flush()
+}
+
+fn markdown_link(title: &str, url: &str) -> String {
+    let title = title.replace('[', "\\[").replace(']', "\\]");
+    // Parentheses and spaces would end the url early.
+    let url = url
+        .replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29");
+    format!("[{}]({})", title, url)
+}
+
+fn open_command(command: &str, url: &str) -> process::Command {
+    let mut words = command.split_whitespace();
+    let mut command = process::Command::new(words.next().unwrap_or("xdg-open"));
+    command.args(words).arg(url);
+    command
+}
+
+fn run_in_terminal<W: Write>(
+    terminal: &mut Terminal<TermionBackend<W>>,
+    raw: &RawTerminal<Stdout>,
+    command: &mut process::Command,
+) -> Result<()> {
+    write!(terminal.backend_mut(), "{}", ToMainScreen)?;
+    Write::flush(terminal.backend_mut())?;
+    raw.suspend_raw_mode()?;
+
+    let status = command.status();
+
+    raw.activate_raw_mode()?;
+    write!(terminal.backend_mut(), "{}", ToAlternateScreen)?;
+    Write::flush(terminal.backend_mut())?;
+    terminal.clear()?;
+
+    status?;
+    Ok(())
+}
+
+/// Create the file for the pager to show the text for `url` in, in a directory
+/// only the user can read, below the cache directory.
+fn pager_file(url: &str) -> Result<(File, PathBuf)> {
+    use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+
+    let dir = base_directories()?.get_cache_home().join("pager");
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .with_context(|| format!("{}", dir.display()))?;
+    // The directory may have been created, less privately, by an older prss.
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("{}", dir.display()))?;
+    let path = dir.join(format!("{:x}.txt", md5::compute(url)));
+    let f = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+        .with_context(|| format!("{}", path.display()))?;
+    Ok((f, path))
+}
+
+/// Write the cached copy of the feed at `url` to a file for the pager, prefixed
+/// by the path of the cache file, and return the path of the file.
+fn dump_cached_feed(url: &str) -> Result<PathBuf> {
+    let (mut f, path) = pager_file(url)?;
+    match get_cached_feed_body(url)? {
+        Some((cache, body)) => {
+            writeln!(f, "Feed: {}\nCache file: {}\n", url, cache.display())?;
+            f.write_all(&body)?;
+        }
+        None => writeln!(f, "Feed: {}\nNo cached copy of this feed", url)?,
+    }
+    Ok(path)
+}
+
+/// Write the header and the text of `entry` to a file to read in a pager, and
+/// return the path of the file.
+fn dump_entry(entry: &FeedListEntry, header: &EntryFormat) -> Result<PathBuf> {
+    let (mut f, path) = pager_file(&entry.url)?;
+    writeln!(f, "{}\n", header.render(entry, Utc::now()))?;
+    match entry.summary.as_deref().map(digest::paragraphs) {
+        Some(text) if !text.is_empty() => writeln!(f, "{}", text)?,
+        _ => writeln!(f, "The feed has no text for this entry.")?,
+    }
+    Ok(path)
+}
+
+/// The smallest terminal the interface is drawn in, whatever `min_width` and
+/// `min_height` are: a row and a column inside the margins.
+const SMALLEST_SIZE: u16 = 3;
+
+/// The message shown instead of the interface, if the terminal is too small
+/// for it.
+fn too_small(size: Rect, config: &Config) -> Option<Paragraph<'static>> {
+    let min_width = config.min_width.max(SMALLEST_SIZE);
+    let min_height = config.min_height.max(SMALLEST_SIZE);
+    if size.width >= min_width && size.height >= min_height {
+        return None;
+    }
+    let text = format!(
+        "The terminal is too small ({}x{}). Enlarge it to at least {}x{}, or press q to quit.",
+        size.width, size.height, min_width, min_height
+    );
+    let paragraph = Paragraph::new(text)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: true });
+    Some(paragraph)
+}
+
+fn confirm<B: Backend>(
+    terminal: &mut Terminal<B>,
+    events: &mut Events,
+    question: &str,
+) -> Result<bool> {
+    loop {
+        terminal.draw(|f| {
+            let rect = f.size();
+            let width = question.len() as u16 + 4;
+            let paragraph = Paragraph::new(question.to_string())
+                .block(Block::default().borders(Borders::ALL))
+                .style(Style::default().fg(Color::White))
+                .wrap(Wrap { trim: true });
+            let area = centered(rect, width, 3 + width / rect.width.max(1));
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph, area);
+        })?;
+
+        match events.next_event() {
+            Some(Event::Key(Ok(Key::Char('y')))) => return Ok(true),
+            Some(Event::Key(Ok(Key::Char('n')))) | Some(Event::Key(Ok(Key::Esc))) | None => {
+                return Ok(false)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn show_onboarding<B: Backend>(
+    terminal: &mut Terminal<B>,
+    events: &mut Events,
+    feeds_txt: &Path,
+) -> Result<()> {
+    loop {
+        terminal.draw(|f| {
+            let rect = f.size().inner(&Margin {
+                vertical: 1,
+                horizontal: 1,
+            });
+
+            let text = vec![
+                Spans::from("You are not subscribed to any feeds yet."),
+                Spans::from(""),
+                Spans::from("Add feed urls to the following file, one url on each line:"),
+                Spans::from(""),
+                Spans::from(format!("    {}", feeds_txt.display())),
+                Spans::from(""),
+                Spans::from("Then start prss again. Press q to quit."),
+            ];
+
+            let paragraph = Paragraph::new(text)
+                .block(
+                    Block::default()
+                        .title("Welcome to prss")
+                        .borders(Borders::ALL),
+                )
+                .style(Style::default().fg(Color::White))
+                .wrap(Wrap { trim: false });
+
+            f.render_widget(paragraph, rect);
+        })?;
+
+        match events.next_event() {
+            Some(Event::Key(Ok(Key::Char('q')))) | Some(Event::Key(Ok(Key::Ctrl('c')))) | None => {
+                return Ok(())
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Run `session`, holding back the message of any panic in it until the
+/// terminal state owned by the session has been dropped, so that the message
+/// ends up on the normal screen instead of a garbled alternate screen.
+async fn restore_terminal_on_panic(session: impl Future<Output = Result<()>>) -> Result<()> {
+    let message = Arc::new(Mutex::new(None));
+    let default_hook = panic::take_hook();
+    {
+        let message = message.clone();
+        panic::set_hook(Box::new(move |info| {
+            let backtrace = Backtrace::capture();
+            let text = match backtrace.status() {
+                BacktraceStatus::Captured => format!("{}\n{}", info, backtrace),
+                _ => info.to_string(),
+            };
+            *message.lock().unwrap() = Some(text);
+        }));
+    }
+
+    let result = AssertUnwindSafe(session).catch_unwind().await;
+
+    let _ = panic::take_hook();
+    panic::set_hook(default_hook);
+    match result {
+        Ok(result) => result,
+        Err(payload) => {
+            // Leaving the alternate screen is buffered in stdout.
+            io::stdout().flush()?;
+            if let Some(message) = message.lock().unwrap().take() {
+                eprintln!("prss crashed: {}", message);
+            }
+            panic::resume_unwind(payload)
         }
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let xdg_dirs = xdg::BaseDirectories::with_prefix("prss")?;
-    let feeds_txt = xdg_dirs
-        .place_config_file("feeds.txt")
-        .expect("cannot create configuration directory");
-    let feeds_txt = File::open(feeds_txt).context("feeds.txt")?;
-    let feed_urls: Vec<String> = process_results(BufReader::new(feeds_txt).lines(), |lines| {
-        lines.filter(|line| !line.starts_with('#')).collect()
+    let opt = Opt::from_args();
+    // The interface would hide the urls printed to stderr.
+    if opt.sequential && !(opt.list || opt.json || opt.digest.is_some()) {
+        bail!("--sequential only works with --list, --json or --digest");
+    }
+
+    let xdg_dirs = base_directories()?;
+    let default_feeds_txt_path = xdg_dirs.place_config_file("feeds.txt").with_context(|| {
+        format!(
+            "could not create the config directory {}; set XDG_CONFIG_HOME",
+            xdg_dirs.get_config_home().display()
+        )
     })?;
+    let config_path = xdg_dirs.get_config_home().join("prss.toml");
+    let config = Config::load(&config_path)?;
+    let read_entries_path = xdg_dirs
+        .place_data_file("read_entries.txt")
+        .with_context(|| {
+            format!(
+                "could not create the data directory {}; set XDG_DATA_HOME",
+                xdg_dirs.get_data_home().display()
+            )
+        })?;
+    if let Some(import) = &opt.import_read {
+        let imported = import_read_entries(import, &read_entries_path)?;
+        if !opt.quiet {
+            eprintln!("Marked {} more entries read", imported);
+        }
+        return Ok(());
+    }
+    if !default_feeds_txt_path.exists() {
+        let mut f = File::create(&default_feeds_txt_path).context("feeds.txt")?;
+        f.write_all(FEEDS_TXT_EXAMPLE.as_bytes())?;
+    }
+    let feeds_txt_path = match &opt.feeds {
+        Some(location) if !is_remote(location) => PathBuf::from(location),
+        _ => default_feeds_txt_path,
+    };
+    let feed_lines = match &opt.feeds {
+        Some(url) if is_remote(url) => match get_remote_feed_urls(url, opt.offline).await {
+            Ok(urls) => urls,
+            Err(_) => read_feed_urls(&feeds_txt_path)?,
+        },
+        _ => read_feed_urls(&feeds_txt_path)?,
+    };
+    let (feed_urls, feed_settings) = parse_feed_lines(&feed_lines)?;
+
+    if opt.purge_cache {
+        let mut keep = feed_urls.clone();
+        keep.extend(opt.feeds.iter().filter(|feeds| is_remote(feeds)).cloned());
+        let (removed, freed) = purge_cache(&keep, opt.older_than)?;
+        if !opt.quiet {
+            eprintln!("Removed {} cache files, freeing {} bytes", removed, freed);
+        }
+        return Ok(());
+    }
+
+    if opt.list || opt.json || opt.digest.is_some() {
+        let quiet = opt.quiet;
+        let (mut feeds, errors) = fetch_feeds(
+            &feed_urls,
+            opt.offline,
+            &feed_settings,
+            &config.fetch_options(),
+            if opt.sequential {
+                1
+            } else {
+                config.max_concurrent_fetches
+            },
+            opt.sequential,
+            |done, total| {
+                if !quiet && !opt.sequential {
+                    eprint!("\rfetched {}/{}...", done, total);
+                }
+            },
+        )
+        .await?;
+        if !quiet && !opt.sequential && !feed_urls.is_empty() {
+            eprintln!();
+        }
+        if !quiet {
+            for error in &errors {
+                eprintln!("{}", error);
+            }
+            for feed in &feeds {
+                for warning in &feed.warnings {
+                    eprintln!("{}: {}", feed.title, warning);
+                }
+            }
+        }
+        let moved = moved_feeds(&opt, &feeds_txt_path, &feeds, config.update_moved_feeds);
+        if !quiet {
+            for moved in &moved {
+                eprintln!("{}", moved);
+            }
+        }
+        sort_feeds(&mut feeds, &feed_urls, config.feed_order);
+        let hidden_path = read_entries_path.with_file_name("hidden.txt");
+        let (read, skipped_read) = get_read_entries(&read_entries_path)?;
+        let (hidden, skipped_hidden) = get_read_entries(&hidden_path)?;
+        if !quiet {
+            for warning in skipped_lines_warning(&read_entries_path, skipped_read)
+                .into_iter()
+                .chain(skipped_lines_warning(&hidden_path, skipped_hidden))
+            {
+                eprintln!("{}", warning);
+            }
+        }
+        let mut feedlist = FeedList::new(feeds, read, &config);
+        feedlist.set_snoozed(get_entry_dates(
+            &read_entries_path.with_file_name("snoozed.txt"),
+        )?);
+        feedlist.set_hidden(hidden);
+        feedlist.set_show_read(opt.all);
+        let since = opt.since;
+        let entries = feedlist
+            .items
+            .iter()
+            .filter(|entry| since.is_none_or(|since| entry.date > since));
+        if let Some(format) = opt.digest {
+            let title = format!("prss digest for {}", Local::today().format("%Y-%m-%d"));
+            let digest = digest::render(format, &title, entries);
+            return if opt.send {
+                send_digest(&config, format, &title, &digest)
+            } else {
+                print!("{}", digest);
+                Ok(())
+            };
+        }
+        let printed = if opt.json {
+            json::print_json(entries, &feedlist.read)
+        } else {
+            print_list(entries)
+        };
+        return match printed {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => Ok(result?),
+        };
+    }
+
+    restore_terminal_on_panic(browse(
+        opt,
+        config,
+        config_path,
+        feed_urls,
+        feed_settings,
+        feeds_txt_path,
+        read_entries_path,
+    ))
+    .await
+}
 
-    let screen = AlternateScreen::from(io::stdout().into_raw_mode()?);
-    let stdin = io::stdin();
+/// Run the interface. The terminal is restored when it returns or panics.
+async fn browse(
+    opt: Opt,
+    mut config: Config,
+    config_path: PathBuf,
+    mut feed_urls: Vec<String>,
+    mut feed_settings: FeedSettingsMap,
+    feeds_txt_path: PathBuf,
+    read_entries_path: PathBuf,
+) -> Result<()> {
+    let raw = io::stdout().into_raw_mode()?;
+    let screen = AlternateScreen::from(io::stdout());
     let backend = TermionBackend::new(screen);
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    let client = reqwest::Client::new();
+    let mut events = Events::new();
 
-    let fetches = futures::stream::iter(feed_urls.iter().map(|url| {
-        let client = client.clone();
-        async move { get_feed_entries(&client, url).await }
-    }))
-    .buffer_unordered(8)
-    .collect::<Vec<_>>()
-    .await;
-    let entries = fetches.into_iter().collect::<Result<Vec<Feed>>>()?;
+    if feed_urls.is_empty() {
+        return show_onboarding(&mut terminal, &mut events, &feeds_txt_path);
+    }
+
+    let (entries, mut errors) =
+        fetch_sorted_feeds(&feed_urls, opt.offline, &feed_settings, &config).await?;
+    let moved = moved_feeds(&opt, &feeds_txt_path, &entries, config.update_moved_feeds);
 
-    let mut events = stdin.keys();
+    let reminders_path = read_entries_path.with_file_name("reminders.txt");
 
-    let mut feedlist = FeedList::new(entries);
+    let (read, skipped_read) = get_read_entries(&read_entries_path)?;
+    let mut feedlist = FeedList::new(entries, read, &config);
+    feedlist.set_reminders(get_entry_dates(&reminders_path)?);
+    let snoozed_path = read_entries_path.with_file_name("snoozed.txt");
+    let mut snoozed = get_entry_dates(&snoozed_path)?;
+    let now = Utc::now();
+    snoozed.retain(|_, until| *until > now);
+    feedlist.set_snoozed(snoozed);
+    let hidden_path = read_entries_path.with_file_name("hidden.txt");
+    let (hidden, skipped_hidden) = get_read_entries(&hidden_path)?;
+    feedlist.set_hidden(hidden);
+    let sort_path = read_entries_path.with_file_name("sort.toml");
+    if let Some(order) = get_sort_order(&sort_path)? {
+        feedlist.set_sort_order(order);
+    }
+    let initially_read = feedlist.read.clone();
+    let mut link_picker: Option<LinkPicker> = None;
+    let mut reading_queue = false;
+    let mut command_line: Option<String> = None;
+    let mut search_line: Option<String> = None;
+    let mut message = skipped_lines_warning(&read_entries_path, skipped_read)
+        .or_else(|| skipped_lines_warning(&hidden_path, skipped_hidden))
+        .or_else(|| Some(moved.join("; ")).filter(|moved| !moved.is_empty()));
+    // The unread entry that is selected, and since when.
+    let mut dwell: Option<(String, Instant)> = None;
+    let mut last_refresh = Instant::now();
+    let mut background_refresh: Option<mpsc::Receiver<Result<Fetched>>> = None;
 
     loop {
+        if let Some(minutes) = config.background_refresh_minutes.filter(|_| !opt.offline) {
+            if background_refresh.is_none()
+                && last_refresh.elapsed() >= Duration::from_secs(minutes * 60)
+            {
+                let (sender, receiver) = mpsc::channel();
+                let feed_urls = feed_urls.clone();
+                let feed_settings = feed_settings.clone();
+                let config = config.clone();
+                tokio::spawn(async move {
+                    let fetch = fetch_sorted_feeds(&feed_urls, false, &feed_settings, &config);
+                    sender.send(fetch.await).ok();
+                });
+                background_refresh = Some(receiver);
+            }
+        }
+        if let Some(receiver) = &background_refresh {
+            let refreshed = match receiver.try_recv() {
+                Ok(refreshed) => Some(refreshed),
+                Err(mpsc::TryRecvError::Empty) => None,
+                Err(mpsc::TryRecvError::Disconnected) => Some(Err(anyhow!("the refresh panicked"))),
+            };
+            if let Some(refreshed) = refreshed {
+                background_refresh = None;
+                last_refresh = Instant::now();
+                // The selection and the read entries are kept, so new entries
+                // only show up where they belong.
+                match refreshed {
+                    Ok((entries, fetch_errors)) => {
+                        let new = feedlist.set_feeds(entries);
+                        errors = fetch_errors;
+                        match new {
+                            0 => {}
+                            1 => message = Some("1 new entry".to_string()),
+                            n => message = Some(format!("{} new entries", n)),
+                        }
+                    }
+                    Err(e) => message = Some(format!("Couldn't refresh the feeds: {}", e)),
+                }
+            }
+        }
+
         terminal.draw(|f| {
-            let rect = f.size().inner(&Margin {
+            let size = f.size();
+            if let Some(too_small) = too_small(size, &config) {
+                f.render_widget(too_small, size);
+                return;
+            }
+            let rect = size.inner(&Margin {
                 vertical: 1,
                 horizontal: 1,
             });
+            let rect = match config.max_width {
+                Some(width) => centered(rect, width, rect.height),
+                None => rect,
+            };
+
+            let items = List::new(list_items(
+                &feedlist,
+                config.max_title_width,
+                &config.entry_format,
+            ))
+            .block(
+                Block::default()
+                    .title({
+                        let mut title = feedlist.title();
+                        if reading_queue {
+                            title.push_str(" (reading queue)");
+                        }
+                        if let Some((position, total)) = feedlist.position() {
+                            title.push_str(&format!(" [{}/{}]", position, total));
+                        }
+                        title
+                    })
+                    .borders(Borders::ALL),
+            )
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().bg(Color::White).fg(Color::Black))
+            .highlight_symbol(&config.highlight_symbol);
 
-            let items: Vec<ListItem> = feedlist
-                .items
-                .iter()
-                .map(|i| ListItem::new(i.title.clone()))
-                .collect();
+            let preview_lines = feedlist.get().map(preview_text).unwrap_or_default();
+            let preview_height = preview_lines.len().max(5) as u16 + 2;
+            let errors_height = match errors.len() {
+                0 => 0,
+                n => n.min(MAX_ERROR_LINES) as u16 + 2,
+            };
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(
+                    [
+                        Constraint::Min(0),
+                        Constraint::Length(errors_height),
+                        Constraint::Length(preview_height),
+                    ]
+                    .as_ref(),
+                )
+                .split(rect);
 
-            let items = List::new(items)
-                .block(Block::default().title("Feed Entries").borders(Borders::ALL))
+            f.render_stateful_widget(items, chunks[0], &mut feedlist.state);
+
+            if !errors.is_empty() {
+                let lines = errors
+                    .iter()
+                    .map(|error| Spans::from(error.clone()))
+                    .collect::<Vec<_>>();
+                let pane = Paragraph::new(lines)
+                    .block(Block::default().title("Errors").borders(Borders::ALL))
+                    .style(Style::default().fg(Color::Red));
+                f.render_widget(pane, chunks[1]);
+            }
+
+            let preview = Paragraph::new(preview_lines)
+                .block(Block::default().borders(Borders::ALL))
                 .style(Style::default().fg(Color::White))
-                .highlight_style(Style::default().bg(Color::White).fg(Color::Black))
-                .highlight_symbol("> ");
+                .wrap(Wrap { trim: true });
+
+            f.render_widget(preview, chunks[2]);
+
+            if let Some(picker) = &mut link_picker {
+                let width = picker.links.iter().map(|l| l.len()).max().unwrap_or(0) + 4;
+                let area = centered(rect, width as u16, picker.links.len() as u16 + 2);
+                let links = picker
+                    .links
+                    .iter()
+                    .map(|link| ListItem::new(link.clone()))
+                    .collect::<Vec<_>>();
+                let list = List::new(links)
+                    .block(Block::default().title("Open link").borders(Borders::ALL))
+                    .style(Style::default().fg(Color::White))
+                    .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+                f.render_widget(Clear, area);
+                f.render_stateful_widget(list, area, &mut picker.state);
+            }
 
-            f.render_stateful_widget(items, rect, &mut feedlist.state);
+            let prompt = match (&command_line, &search_line, &message) {
+                (Some(line), _, _) => Some(format!(":{}", line)),
+                (None, Some(line), _) => Some(format!("/{}", line)),
+                (None, None, Some(message)) => Some(message.clone()),
+                (None, None, None) => None,
+            };
+            if let Some(prompt) = prompt {
+                // As wide as the interface with its margins, within the terminal.
+                let x = rect.x.saturating_sub(1).max(size.x);
+                let width = rect.width.saturating_add(2).min(size.right() - x);
+                let area = Rect::new(x, size.bottom().saturating_sub(1), width, 1);
+                f.render_widget(Paragraph::new(prompt), area);
+            }
         })?;
 
-        match events.next() {
-            Some(Ok(Key::Char('q'))) => break,
-            Some(Ok(Key::Down)) | Some(Ok(Key::Char('j'))) | Some(Ok(Key::Char('n'))) => {
-                feedlist.next();
+        let selected = feedlist
+            .get()
+            .filter(|e| !feedlist.is_read(e))
+            .map(|e| e.url.clone());
+        if dwell.as_ref().map(|(url, _)| url) != selected.as_ref() {
+            dwell = selected.map(|url| (url, Instant::now()));
+        }
+        let mark_read_at = match (config.auto_mark_read_seconds, &dwell) {
+            (Some(seconds), Some((_, since))) => Some(*since + Duration::from_secs(seconds)),
+            _ => None,
+        };
+        let timeout = mark_read_at.map_or(TICK_INTERVAL, |at| {
+            at.saturating_duration_since(Instant::now())
+                .min(TICK_INTERVAL)
+        });
+
+        // The screen is drawn again on every tick, so that things that change
+        // with time, such as relative dates, stay up to date.
+        let key = match events.next_event_timeout(timeout) {
+            Some(Event::Resize) => continue,
+            Some(Event::Tick) => {
+                feedlist.update_today();
+                // Moving on to another entry starts over, so entries that are
+                // only scrolled past aren't marked read.
+                if mark_read_at.is_some_and(|at| at <= Instant::now()) {
+                    if let Some((url, _)) = dwell.take() {
+                        feedlist.mark_read_in_place(url);
+                        persist_read_entries(&read_entries_path, &feedlist.read)?;
+                    }
+                }
+                continue;
             }
-            Some(Ok(Key::Up)) | Some(Ok(Key::Char('k'))) | Some(Ok(Key::Char('p'))) => {
-                feedlist.previous();
+            Some(Event::Key(key)) => Some(key),
+            None => None,
+        };
+
+        message = None;
+        let mut command = None;
+        if let Some(line) = &mut command_line {
+            match key {
+                Some(Ok(Key::Char('\n'))) => {
+                    match Command::parse(&command_line.take().unwrap_or_default()) {
+                        Ok(parsed) => command = Some(parsed),
+                        Err(e) => message = Some(e),
+                    }
+                }
+                Some(Ok(Key::Char(c))) => line.push(c),
+                Some(Ok(Key::Backspace)) if line.is_empty() => command_line = None,
+                Some(Ok(Key::Backspace)) => {
+                    line.pop();
+                }
+                Some(Ok(Key::Esc)) => command_line = None,
+                Some(Ok(Key::Ctrl('c'))) => break,
+                _ => {}
+            }
+        } else if let Some(line) = &mut search_line {
+            // The list is filtered as the query is typed.
+            match key {
+                Some(Ok(Key::Char('\n'))) => search_line = None,
+                Some(Ok(Key::Char(c))) => {
+                    line.push(c);
+                    feedlist.set_search(Some(line.clone()));
+                }
+                Some(Ok(Key::Backspace)) if line.is_empty() => {
+                    search_line = None;
+                    feedlist.set_search(None);
+                }
+                Some(Ok(Key::Backspace)) => {
+                    line.pop();
+                    feedlist.set_search(Some(line.clone()));
+                }
+                Some(Ok(Key::Esc)) => {
+                    search_line = None;
+                    feedlist.set_search(None);
+                }
+                Some(Ok(Key::Ctrl('c'))) => break,
+                _ => {}
             }
-            Some(Ok(Key::Char('\n'))) => {
-                let url = feedlist.get().url.clone();
-                Command::new("xdg-open")
-                    .arg(url)
-                    .status()
-                    .unwrap_or_else(|e| panic!("Failed to open link: {}", e));
+        } else if let Some(picker) = &mut link_picker {
+            match key {
+                Some(Ok(Key::Down)) | Some(Ok(Key::Char('j'))) | Some(Ok(Key::Char('n'))) => {
+                    picker.next();
+                }
+                Some(Ok(Key::Up)) | Some(Ok(Key::Char('k'))) | Some(Ok(Key::Char('p'))) => {
+                    picker.previous();
+                }
+                Some(Ok(Key::Char('\n'))) => {
+                    if let Err(e) = open_in_browser(&config.browser, picker.selected()) {
+                        message = Some(e.to_string());
+                    }
+                    link_picker = None;
+                }
+                Some(Ok(Key::Esc)) | Some(Ok(Key::Char('q'))) => link_picker = None,
+                Some(Ok(Key::Ctrl('c'))) => break,
+                _ => {}
+            }
+        } else {
+            match key {
+                Some(Ok(Key::Char('q'))) => break,
+                Some(Ok(Key::Char(':'))) => command_line = Some(String::new()),
+                Some(Ok(Key::Char('/'))) => {
+                    search_line = Some(feedlist.search().unwrap_or_default().to_string());
+                }
+                Some(Ok(Key::Char('R'))) => command = Some(Command::Refresh),
+                Some(Ok(Key::Char('O'))) => command = Some(Command::OpenRecent(None)),
+                Some(Ok(Key::Ctrl('r'))) => command = Some(Command::Reload),
+                Some(Ok(Key::Down)) | Some(Ok(Key::Char('j'))) | Some(Ok(Key::Char('n'))) => {
+                    feedlist.next();
+                }
+                Some(Ok(Key::Up)) | Some(Ok(Key::Char('k'))) | Some(Ok(Key::Char('p'))) => {
+                    feedlist.previous();
+                }
+                Some(Ok(Key::Char('N'))) => {
+                    feedlist.next_unread();
+                }
+                Some(Ok(Key::Char('P'))) => {
+                    feedlist.previous_unread();
+                }
+                Some(Ok(Key::Char('a'))) => {
+                    feedlist.cycle_author();
+                }
+                Some(Ok(Key::Char('l'))) => match feedlist.get() {
+                    Some(entry) if feedlist.reminders.contains_key(&entry.url) => {
+                        command = Some(Command::Unremind);
+                    }
+                    Some(_) => command = Some(Command::Remind(DEFAULT_REMINDER)),
+                    None => {}
+                },
+                Some(Ok(Key::Char('z'))) => command = Some(Command::Snooze(config.snooze)),
+                Some(Ok(Key::Char('x'))) => command = Some(Command::Hide),
+                Some(Ok(Key::Char('u'))) => command = Some(Command::Undo),
+                Some(Ok(Key::Char('m'))) => {
+                    feedlist.mark_read_up_to_selected();
+                    persist_read_entries(&read_entries_path, &feedlist.read)?;
+                }
+                Some(Ok(Key::Char('f'))) => {
+                    feedlist.mark_feed_read();
+                    persist_read_entries(&read_entries_path, &feedlist.read)?;
+                }
+                Some(Ok(Key::Char('K'))) => {
+                    feedlist.move_feed(true);
+                }
+                Some(Ok(Key::Char('J'))) => {
+                    feedlist.move_feed(false);
+                }
+                Some(Ok(Key::Char('s'))) => {
+                    feedlist.toggle_show_read();
+                }
+                Some(Ok(Key::Char('U'))) => {
+                    feedlist.toggle_unread_first();
+                }
+                Some(Ok(Key::Char('o'))) => {
+                    feedlist.flip_sort_direction();
+                    persist_sort_order(&sort_path, feedlist.sort_order())?;
+                }
+                Some(Ok(Key::Char('M'))) => {
+                    feedlist.toggle_muting();
+                }
+                Some(Ok(Key::Char('g'))) => {
+                    feedlist.toggle_view(View::Grouped);
+                }
+                Some(Ok(Key::Char('d'))) => {
+                    feedlist.toggle_view(View::Summary);
+                }
+                Some(Ok(Key::Char('r'))) => {
+                    reading_queue = !reading_queue;
+                    if reading_queue && feedlist.get().is_none_or(|e| feedlist.is_read(e)) {
+                        feedlist.next_unread();
+                    }
+                }
+                Some(Ok(Key::Esc)) if reading_queue => reading_queue = false,
+                Some(Ok(Key::Char('\n'))) if reading_queue => {
+                    if let Some(entry) = feedlist.get().filter(|e| !feedlist.is_read(e)) {
+                        match open_in_browser(&config.browser, &entry.url) {
+                            Ok(()) => {
+                                feedlist.mark_selected_read_and_advance();
+                                persist_read_entries(&read_entries_path, &feedlist.read)?;
+                            }
+                            Err(e) => message = Some(e.to_string()),
+                        }
+                    }
+                }
+                Some(Ok(Key::Backspace)) | Some(Ok(Key::Esc)) => {
+                    feedlist.drill_out();
+                }
+                Some(Ok(Key::Char(']'))) => {
+                    feedlist.next_feed();
+                }
+                Some(Ok(Key::Char('['))) => {
+                    feedlist.previous_feed();
+                }
+                Some(Ok(Key::Char(' '))) => {
+                    feedlist.toggle_expanded();
+                }
+                Some(Ok(Key::Char('\n'))) if feedlist.drill_in() => {}
+                Some(Ok(Key::Char('\n'))) if !feedlist.toggle_expanded() => {
+                    command = Some(Command::Open);
+                }
+                Some(Ok(Key::Char('H'))) => {
+                    if let Some(homepage) = feedlist.get().and_then(|e| e.homepage.as_deref()) {
+                        if let Err(e) = open_in_browser(&config.browser, homepage) {
+                            message = Some(e.to_string());
+                        }
+                    }
+                }
+                Some(Ok(Key::Char('y'))) => {
+                    if let Some(entry) = feedlist.get() {
+                        copy_to_clipboard(terminal.backend_mut(), &entry.url)?;
+                    }
+                }
+                Some(Ok(Key::Char('Y'))) => {
+                    if let Some(entry) = feedlist.get() {
+                        copy_to_clipboard(
+                            terminal.backend_mut(),
+                            &markdown_link(&entry.title, &entry.url),
+                        )?;
+                    }
+                }
+                Some(Ok(Key::Char('w'))) => {
+                    if let Some(entry) = feedlist.get() {
+                        let mut command = open_command(&config.terminal_browser, &entry.url);
+                        if let Err(e) = run_in_terminal(&mut terminal, &raw, &mut command) {
+                            message = Some(format!(
+                                "Failed to run {}: {:#}",
+                                config.terminal_browser, e
+                            ));
+                        }
+                    }
+                }
+                Some(Ok(Key::Char('D'))) => {
+                    if let Some(entry) = feedlist.get() {
+                        let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+                        match dump_cached_feed(&entry.feed_url) {
+                            Ok(dump) => {
+                                let mut command = open_command(&pager, &dump.to_string_lossy());
+                                if let Err(e) = run_in_terminal(&mut terminal, &raw, &mut command) {
+                                    message = Some(format!("Failed to run {}: {:#}", pager, e));
+                                }
+                            }
+                            Err(e) => {
+                                message = Some(format!("Couldn't dump the cached feed: {:#}", e))
+                            }
+                        }
+                    }
+                }
+                Some(Ok(Key::Char('v'))) => {
+                    if let Some(entry) = feedlist.get() {
+                        let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+                        match dump_entry(entry, &config.pager_header) {
+                            Ok(dump) => {
+                                let mut command = open_command(&pager, &dump.to_string_lossy());
+                                if let Err(e) = run_in_terminal(&mut terminal, &raw, &mut command) {
+                                    message = Some(format!("Failed to run {}: {:#}", pager, e));
+                                }
+                            }
+                            Err(e) => message = Some(format!("Couldn't dump the entry: {:#}", e)),
+                        }
+                    }
+                }
+                Some(Ok(Key::Char('C'))) => command = Some(Command::CachePath),
+                Some(Ok(Key::Char('c'))) => command = Some(Command::OpenComments),
+                Some(Ok(Key::Ctrl('c'))) => break,
+                _ => {}
             }
-            Some(Ok(Key::Ctrl('c'))) => break,
-            _ => {}
+        }
+
+        match command {
+            Some(Command::Open) => match feedlist.get() {
+                Some(entry) if entry.links.len() > 1 => {
+                    link_picker = Some(LinkPicker::new(entry.links.clone()));
+                }
+                Some(entry) => {
+                    if let Err(e) = open_in_browser(&config.browser, &entry.url) {
+                        message = Some(e.to_string());
+                    }
+                }
+                None => {}
+            },
+            Some(Command::OpenRecent(n)) => {
+                let entries = feedlist.recent_unread(n.unwrap_or(config.open_recent));
+                let question = format!("Open {} entries? (y/n)", entries.len());
+                if entries.len() <= config.confirm_open_above
+                    || confirm(&mut terminal, &mut events, &question)?
+                {
+                    let mut opened = vec![];
+                    for (i, entry) in entries.iter().enumerate() {
+                        if i > 0 {
+                            tokio::time::sleep(BATCH_OPEN_INTERVAL).await;
+                        }
+                        match open_in_browser(&config.browser, &entry.url) {
+                            Ok(()) => opened.push(entry.url.clone()),
+                            Err(e) => {
+                                message = Some(e.to_string());
+                                break;
+                            }
+                        }
+                    }
+                    if message.is_none() {
+                        message = Some(format!("Opened {} entries", opened.len()));
+                    }
+                    feedlist.mark_read(opened);
+                    persist_read_entries(&read_entries_path, &feedlist.read)?;
+                }
+            }
+            Some(Command::OpenComments) if feedlist.get().is_some() => {
+                match feedlist
+                    .get()
+                    .and_then(|entry| entry.comments_url.as_deref())
+                {
+                    Some(url) => {
+                        if let Err(e) = open_in_browser(&config.browser, url) {
+                            message = Some(e.to_string());
+                        }
+                    }
+                    None => message = Some("The entry has no comments link".to_string()),
+                }
+            }
+            Some(Command::OpenComments) => {}
+            Some(Command::MarkRead) => {
+                feedlist.mark_selected_read_and_advance();
+                persist_read_entries(&read_entries_path, &feedlist.read)?;
+            }
+            Some(Command::Refresh) => {
+                match fetch_sorted_feeds(&feed_urls, opt.offline, &feed_settings, &config).await {
+                    Ok((entries, fetch_errors)) => {
+                        feedlist.set_feeds(entries);
+                        errors = fetch_errors;
+                    }
+                    Err(e) => message = Some(format!("Couldn't refresh the feeds: {}", e)),
+                }
+                last_refresh = Instant::now();
+            }
+            Some(Command::Reload) => match Config::load(&config_path) {
+                Ok(reloaded) => {
+                    config = reloaded;
+                    feedlist.set_config(&config);
+                    message = Some(format!("Reloaded {}", config_path.display()));
+                }
+                Err(e) => message = Some(format!("Couldn't reload the config: {:#}", e)),
+            },
+            Some(Command::Add(url)) => {
+                message = Some(match add_feed(&opt, &feeds_txt_path, &feed_urls, &url) {
+                    Ok(()) => {
+                        feed_urls.push(url.clone());
+                        feed_settings.insert(url.clone(), FeedSettings::default());
+                        match fetch_sorted_feeds(&feed_urls, opt.offline, &feed_settings, &config)
+                            .await
+                        {
+                            Ok((entries, fetch_errors)) => {
+                                feedlist.set_feeds(entries);
+                                errors = fetch_errors;
+                                format!("Added {}", url)
+                            }
+                            Err(e) => format!("Added {}, but couldn't fetch it: {}", url, e),
+                        }
+                    }
+                    Err(e) => e.to_string(),
+                });
+            }
+            Some(Command::Remind(after)) if feedlist.get().is_some() => match from_now(after) {
+                Some(date) => {
+                    feedlist.set_reminder(Some(date));
+                    persist_entry_dates(&reminders_path, &feedlist.reminders)?;
+                    message = Some(format!(
+                        "Reminding you on {}",
+                        date.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                    ));
+                }
+                None => message = Some("The reminder is too far off".to_string()),
+            },
+            Some(Command::Unremind) if feedlist.get().is_some() => {
+                feedlist.set_reminder(None);
+                persist_entry_dates(&reminders_path, &feedlist.reminders)?;
+                message = Some("Cleared the reminder".to_string());
+            }
+            Some(Command::Snooze(duration)) if feedlist.get().is_some() => {
+                if let Some(until) = from_now(duration) {
+                    feedlist.snooze_selected(until);
+                    persist_entry_dates(&snoozed_path, &feedlist.snoozed)?;
+                    persist_read_entries(&read_entries_path, &feedlist.read)?;
+                    message = Some(format!(
+                        "Snoozed until {}",
+                        until.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                    ));
+                } else {
+                    message = Some("The snooze is too long".to_string());
+                }
+            }
+            Some(Command::CachePath) | Some(Command::OpenCacheDir) if feedlist.get().is_some() => {
+                let feed_url = feedlist
+                    .get()
+                    .map(|e| e.feed_url.clone())
+                    .unwrap_or_default();
+                message = Some(match cached_feed_path(&feed_url) {
+                    Ok(Some(path)) if command == Some(Command::CachePath) => {
+                        copy_to_clipboard(terminal.backend_mut(), &path.to_string_lossy())?;
+                        format!("Copied {}", path.display())
+                    }
+                    Ok(Some(path)) => {
+                        let dir = path.parent().unwrap_or(&path).to_string_lossy();
+                        match open_in_browser(&config.browser, &dir) {
+                            Ok(()) => format!("Opened {}", dir),
+                            Err(e) => e.to_string(),
+                        }
+                    }
+                    Ok(None) => format!("{} isn't cached", feed_url),
+                    Err(e) => e.to_string(),
+                });
+            }
+            Some(Command::Hide) if feedlist.get().is_some() => {
+                feedlist.hide_selected();
+                persist_read_entries(&hidden_path, &feedlist.hidden)?;
+                message = Some(format!(
+                    "Hidden for good, until it is removed from {}",
+                    hidden_path.display()
+                ));
+            }
+            Some(Command::Undo) => match feedlist.undo() {
+                Some(action) => {
+                    persist_read_entries(&read_entries_path, &feedlist.read)?;
+                    persist_entry_dates(&snoozed_path, &feedlist.snoozed)?;
+                    persist_read_entries(&hidden_path, &feedlist.hidden)?;
+                    message = Some(format!("Undid {}", action));
+                }
+                None => message = Some("Nothing to undo".to_string()),
+            },
+            Some(Command::Remind(_))
+            | Some(Command::Unremind)
+            | Some(Command::Snooze(_))
+            | Some(Command::Hide)
+            | Some(Command::CachePath)
+            | Some(Command::OpenCacheDir) => {}
+            Some(Command::Disable) | Some(Command::Enable) if feedlist.get().is_some() => {
+                let disabled = command == Some(Command::Disable);
+                let url = feedlist
+                    .get()
+                    .map(|e| e.feed_url.clone())
+                    .unwrap_or_default();
+                message = Some(
+                    match set_feed_disabled(&opt, &feeds_txt_path, &url, disabled) {
+                        Ok(()) => {
+                            feed_settings.entry(url.clone()).or_default().disabled = disabled;
+                            if disabled {
+                                format!("Disabled {}, showing its cached entries", url)
+                            } else {
+                                format!("Enabled {}, fetching it again with R", url)
+                            }
+                        }
+                        Err(e) => e.to_string(),
+                    },
+                );
+            }
+            Some(Command::Disable) | Some(Command::Enable) => {}
+            Some(Command::Sort(SortBy::Date)) => {
+                feedlist.set_sort(SortKey::Date, false);
+                persist_sort_order(&sort_path, feedlist.sort_order())?;
+            }
+            Some(Command::Sort(SortBy::Feed)) => {
+                feedlist.set_sort(SortKey::Feed, false);
+                persist_sort_order(&sort_path, feedlist.sort_order())?;
+            }
+            Some(Command::Sort(SortBy::Title)) => {
+                feedlist.set_sort(SortKey::Title, false);
+                persist_sort_order(&sort_path, feedlist.sort_order())?;
+            }
+            Some(Command::Sort(SortBy::Unread)) => feedlist.set_unread_first(true),
+            Some(Command::Quit) => break,
+            None => {}
+        }
+    }
+
+    let remote_feeds = opt.feeds.as_deref().is_some_and(is_remote);
+    if feedlist.reordered && !remote_feeds {
+        let question = format!(
+            "Save the new order of feeds to {}? (y/n)",
+            feeds_txt_path.display()
+        );
+        if confirm(&mut terminal, &mut events, &question)? {
+            persist_feed_order(&feeds_txt_path, &feedlist.feed_urls())?;
         }
     }
+
+    if !opt.quiet {
+        let marked_read = feedlist.read.difference(&initially_read).count();
+        let summary = exit_summary(&feedlist, marked_read, &errors);
+        // Leave the alternate screen and raw mode first, so that the summary
+        // stays in the terminal.
+        drop(terminal);
+        drop(raw);
+        print!("{}", summary);
+    }
     Ok(())
 }
+
+/// What is printed when quitting the interface, such as
+/// `12 feeds, 340 entries, 25 unread, 3 marked read`, followed by the feeds
+/// that couldn't be fetched.
+fn exit_summary(feedlist: &FeedList, marked_read: usize, errors: &[String]) -> String {
+    let unread = feedlist
+        .feeds
+        .iter()
+        .map(|feed| feedlist.unread_count(feed))
+        .sum::<usize>();
+    let count = |n: usize, one: &str, many: &str| match n {
+        1 => format!("1 {}", one),
+        n => format!("{} {}", n, many),
+    };
+    let mut summary = format!(
+        "{}, {}, {} unread, {} marked read\n",
+        count(feedlist.feeds.len(), "feed", "feeds"),
+        count(feedlist.entry_count(), "entry", "entries"),
+        unread,
+        marked_read
+    );
+    if !errors.is_empty() {
+        summary.push_str(&format!(
+            "{} failed:\n",
+            count(errors.len(), "feed", "feeds")
+        ));
+        for error in errors {
+            summary.push_str(&format!("  {}\n", error));
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_read_entries_skipped() {
+        let path = std::env::temp_dir().join(format!("prss-read-{}.txt", std::process::id()));
+        fs::write(
+            &path,
+            b"https://example.com/a\nhttps://example.com/\xff\xfe\nhttps://example.com/b\r\n",
+        )
+        .unwrap();
+        let (read, skipped) = get_read_entries(&path).unwrap();
+        assert_eq!(skipped, 1);
+        let mut urls = read.iter().map(String::as_str).collect::<Vec<_>>();
+        urls.sort();
+        assert_eq!(urls, ["https://example.com/a", "https://example.com/b"]);
+
+        persist_read_entries(&path, &read).unwrap();
+        assert_eq!(get_read_entries(&path).unwrap(), (read, 0));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn feed_line_annotations() {
+        let (url, settings) =
+            parse_feed_line("https://example.com/feed.xml disabled timeout:10s").unwrap();
+        assert_eq!(url, "https://example.com/feed.xml");
+        assert!(settings.disabled);
+        assert_eq!(settings.timeout, Some(Duration::from_secs(10)));
+
+        let (url, settings) = parse_feed_line("https://example.com/disabled.xml").unwrap();
+        assert_eq!(url, "https://example.com/disabled.xml");
+        assert!(!settings.disabled);
+
+        assert!(parse_feed_line("https://example.com/feed.xml disabled yes").is_err());
+
+        let (_, settings) = parse_feed_line("https://example.com/feed.xml format:rss").unwrap();
+        assert_eq!(settings.format, Some(FeedFormat::Rss));
+        assert!(parse_feed_line("https://example.com/feed.xml format:json").is_err());
+    }
+
+    #[test]
+    fn durations_too_long() {
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            Duration::from_secs(14 * 24 * 60 * 60)
+        );
+        assert!(parse_duration("999999999999999999w").is_err());
+        assert!(parse_since("100000000w").is_err());
+        assert!(parse_since("1d").unwrap() < Utc::now());
+    }
+
+    #[test]
+    fn markdown_links() {
+        assert_eq!(
+            markdown_link(
+                "[Rust] (again)",
+                "https://en.wikipedia.org/wiki/Rust_(language) x"
+            ),
+            "[\\[Rust\\] (again)](https://en.wikipedia.org/wiki/Rust_%28language%29%20x)"
+        );
+    }
+
+    #[test]
+    fn elide_wide_characters() {
+        assert_eq!(elide_middle("short", 10), "short");
+        assert_eq!(elide_middle("abcdefghij", 5), "ab…ij");
+
+        let cjk = elide_middle("日本語のタイトルです", 9);
+        assert_eq!(cjk, "日本…です");
+        assert!(cjk.width() <= 9);
+
+        let emoji = elide_middle("🎉🎉🎉 Release party 🎉🎉🎉", 11);
+        assert_eq!(emoji, "🎉🎉…🎉🎉");
+        assert!(emoji.width() <= 11);
+
+        assert_eq!(elide_middle("日本語", 1), "…");
+    }
+}
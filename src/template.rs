@@ -0,0 +1,263 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::feedlist::FeedListEntry;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Feed,
+    Date,
+    Author,
+    RelativeDate,
+    Comments,
+    Categories,
+    Url,
+}
+
+const FIELDS: [(&str, Field); 8] = [
+    ("title", Field::Title),
+    ("feed", Field::Feed),
+    ("date", Field::Date),
+    ("author", Field::Author),
+    ("relative_date", Field::RelativeDate),
+    ("comments", Field::Comments),
+    ("categories", Field::Categories),
+    ("url", Field::Url),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Part {
+    Text(String),
+    Field(Field),
+}
+
+/// How an entry is shown in the list, such as `{title} ({feed})`. Literal braces
+/// are written as `{{` and `}}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryFormat(pub Vec<Part>);
+
+#[derive(Debug)]
+pub struct InvalidFormat(String);
+
+impl fmt::Display for InvalidFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let available = FIELDS
+            .iter()
+            .map(|(name, _)| format!("{{{}}}", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "{}; the available placeholders are {}",
+            self.0, available
+        )
+    }
+}
+
+impl std::error::Error for InvalidFormat {}
+
+impl FromStr for EntryFormat {
+    type Err = InvalidFormat;
+
+    fn from_str(format: &str) -> Result<EntryFormat, InvalidFormat> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(InvalidFormat(format!("Unclosed {{{}", name)));
+                            }
+                        }
+                    }
+                    let field = FIELDS
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, field)| *field)
+                        .ok_or_else(|| {
+                            InvalidFormat(format!("Unknown placeholder {{{}}}", name))
+                        })?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err(InvalidFormat("Unmatched } in the format".to_string())),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(EntryFormat(parts))
+    }
+}
+
+impl EntryFormat {
+    /// The text of the format for `entry`, leaving out lines whose placeholders
+    /// are all empty, such as `Author: {author}` for an entry without an author.
+    pub fn render(&self, entry: &FeedListEntry, now: DateTime<Utc>) -> String {
+        let mut lines = vec![];
+        // The text of the current line, whether it has placeholders and
+        // whether any of them are filled in.
+        let mut line = (String::new(), false, false);
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => {
+                    let mut rest = text.split('\n');
+                    line.0.push_str(rest.next().unwrap_or_default());
+                    for next in rest {
+                        lines.push(std::mem::replace(
+                            &mut line,
+                            (next.to_string(), false, false),
+                        ));
+                    }
+                }
+                Part::Field(field) => {
+                    let value = field.value(entry, now);
+                    line.1 = true;
+                    line.2 |= !value.is_empty();
+                    line.0.push_str(&value);
+                }
+            }
+        }
+        lines.push(line);
+        lines
+            .into_iter()
+            .filter(|(_, placeholders, filled)| !placeholders || *filled)
+            .map(|(text, _, _)| text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Default for EntryFormat {
+    fn default() -> EntryFormat {
+        "{title} ({feed})".parse().unwrap()
+    }
+}
+
+/// How long ago `date` was, such as `5m ago` or `3d ago`.
+pub fn relative_date(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let age = now - date;
+    if age.num_minutes() < 1 {
+        "just now".to_string()
+    } else if age.num_hours() < 1 {
+        format!("{}m ago", age.num_minutes())
+    } else if age.num_days() < 1 {
+        format!("{}h ago", age.num_hours())
+    } else if age.num_weeks() < 1 {
+        format!("{}d ago", age.num_days())
+    } else {
+        format!("{}w ago", age.num_weeks())
+    }
+}
+
+impl Field {
+    /// The text of the field for `entry`, apart from the title, which is
+    /// shortened by the caller.
+    pub fn value(self, entry: &FeedListEntry, now: DateTime<Utc>) -> String {
+        match self {
+            Field::Title => entry.title.clone(),
+            Field::Feed => entry.feed_title.clone(),
+            Field::Date => entry.date.format("%Y-%m-%d %H:%M").to_string(),
+            Field::Author => entry.author.clone().unwrap_or_default(),
+            Field::RelativeDate => relative_date(entry.date, now),
+            Field::Comments => match entry.comments {
+                None => String::new(),
+                Some(1) => "1 comment".to_string(),
+                Some(n) => format!("{} comments", n),
+            },
+            Field::Categories => entry.categories.join(", "),
+            Field::Url => entry.url.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "{relative_date}  {title} {{{feed}}}"
+                .parse::<EntryFormat>()
+                .unwrap(),
+            EntryFormat(vec![
+                Part::Field(Field::RelativeDate),
+                Part::Text("  ".to_string()),
+                Part::Field(Field::Title),
+                Part::Text(" {".to_string()),
+                Part::Field(Field::Feed),
+                Part::Text("}".to_string()),
+            ])
+        );
+        assert_eq!(
+            "{title".parse::<EntryFormat>().unwrap_err().0,
+            "Unclosed {title"
+        );
+        let error = "{title} {site}".parse::<EntryFormat>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown placeholder {site}; the available placeholders are {title}, {feed}, \
+             {date}, {author}, {relative_date}, {comments}, {categories}, {url}"
+        );
+    }
+
+    #[test]
+    fn render() {
+        let entry = FeedListEntry {
+            title: "Hello".to_string(),
+            feed_title: "Blog".to_string(),
+            feed_url: "https://example.com/feed.xml".to_string(),
+            hub: None,
+            homepage: None,
+            url: "https://example.com/hello".to_string(),
+            links: vec![],
+            date: Utc.ymd(2021, 2, 3).and_hms(10, 0, 0),
+            author: None,
+            summary: None,
+            image_url: None,
+            comments: None,
+            comments_url: None,
+            categories: vec![],
+        };
+        let format = "{title}\nAuthor: {author}\n\n{feed}, {date}\n{url}"
+            .parse::<EntryFormat>()
+            .unwrap();
+        assert_eq!(
+            format.render(&entry, entry.date),
+            "Hello\n\nBlog, 2021-02-03 10:00\nhttps://example.com/hello"
+        );
+    }
+
+    #[test]
+    fn relative_dates() {
+        let now = Utc.ymd(2021, 2, 10).and_hms(12, 0, 0);
+        let ago = |seconds| relative_date(now - chrono::Duration::seconds(seconds), now);
+        assert_eq!(ago(30), "just now");
+        assert_eq!(ago(5 * 60), "5m ago");
+        assert_eq!(ago(3 * 60 * 60), "3h ago");
+        assert_eq!(ago(2 * 24 * 60 * 60), "2d ago");
+        assert_eq!(ago(15 * 24 * 60 * 60), "2w ago");
+    }
+}
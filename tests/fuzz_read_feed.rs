@@ -0,0 +1,162 @@
+//! Feeds random and mutated documents to `read_feed`, which must return an
+//! error instead of panicking on anything a server sends.
+//!
+//! The number of documents and the seed can be set with `PRSS_FUZZ_ITERATIONS`
+//! and `PRSS_FUZZ_SEED`, such as for a longer run after changing the parser:
+//!
+//!     PRSS_FUZZ_ITERATIONS=200000 cargo test --release --test fuzz_read_feed
+
+use std::panic;
+use std::sync::{Arc, Mutex};
+
+use prss::{read_feed, read_feed_with_hint, FeedFormat};
+
+/// Pieces of feeds that are inserted into the documents, to reach the code for
+/// the elements rather than only the XML parser.
+const TOKENS: &[&str] = &[
+    "<",
+    ">",
+    "&",
+    "&amp;",
+    "&#x0;",
+    "]]>",
+    "<![CDATA[",
+    "\"",
+    "<entry>",
+    "</entry>",
+    "<item>",
+    "</item>",
+    "<link/>",
+    "<link href=\"\"/>",
+    "<link rel=\"alternate\" href=\"/x\"/>",
+    "<link rel=\"replies\" href=\"::\"/>",
+    "<link rel=\"next\" href=\"?page=2\"/>",
+    "<link></link>",
+    "<guid isPermaLink=\"true\">http://</guid>",
+    "<pubDate></pubDate>",
+    "<pubDate>Mon, 99 Foo 2021 99:99:99 +9999</pubDate>",
+    "<dc:date>2021-13-45</dc:date>",
+    "<updated>garbage</updated>",
+    "<slash:comments>-1</slash:comments>",
+    "<thr:total>99999999999999999999999</thr:total>",
+    "<media:thumbnail url=\"\"/>",
+    "<enclosure url=\"\" type=\"image/\"/>",
+    "<category/>",
+    " xml:base=\"::\"",
+    "<title></title>",
+    "\u{feff}",
+    "\u{0130}",
+];
+
+/// A small xorshift generator, so that failures can be reproduced from the
+/// seed without depending on a random number crate.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+fn fixtures() -> Vec<Vec<u8>> {
+    let dir = format!("{}/tests/fixtures", env!("CARGO_MANIFEST_DIR"));
+    let mut paths = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| std::fs::read(path).unwrap())
+        .collect()
+}
+
+fn mutate(rng: &mut Rng, document: &mut Vec<u8>) {
+    for _ in 0..=rng.below(8) {
+        let at = rng.below(document.len() + 1);
+        match rng.below(6) {
+            0 if at < document.len() => document[at] = rng.next() as u8,
+            1 => {
+                let end = (at + rng.below(64)).min(document.len());
+                document.drain(at..end);
+            }
+            2 => {
+                let end = (at + rng.below(256)).min(document.len());
+                let copy = document[at..end].to_vec();
+                let to = rng.below(document.len() + 1);
+                document.splice(to..to, copy);
+            }
+            3 => document.truncate(at),
+            _ => {
+                let token = TOKENS[rng.below(TOKENS.len())].as_bytes();
+                document.splice(at..at, token.iter().copied());
+            }
+        }
+    }
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[test]
+fn read_feed_never_panics() {
+    let iterations = env_or("PRSS_FUZZ_ITERATIONS", 2000);
+    let seed = env_or("PRSS_FUZZ_SEED", 0x5eed_f00d).max(1);
+    let fixtures = fixtures();
+    let mut rng = Rng(seed);
+
+    // The messages of panics, with where they happened, are printed when the
+    // test fails instead.
+    let panicked = Arc::new(Mutex::new(None));
+    let default_hook = panic::take_hook();
+    {
+        let panicked = panicked.clone();
+        panic::set_hook(Box::new(move |info| {
+            *panicked.lock().unwrap() = Some(info.to_string());
+        }));
+    }
+    let mut failures = vec![];
+    for i in 0..iterations {
+        let document = if i % 10 == 0 {
+            (0..rng.below(512)).map(|_| rng.next() as u8).collect()
+        } else {
+            let mut document = fixtures[rng.below(fixtures.len())].clone();
+            mutate(&mut rng, &mut document);
+            document
+        };
+        let read = panic::catch_unwind(|| {
+            let _ = read_feed("https://example.com/feed.xml", &document);
+            let _ = read_feed_with_hint("feed.xml", &document, Some(FeedFormat::Rss));
+        });
+        if read.is_err() {
+            let message = panicked.lock().unwrap().take().unwrap_or_default();
+            failures.push(format!(
+                "document {}: {}\n{}",
+                i,
+                message,
+                String::from_utf8_lossy(&document)
+            ));
+        }
+    }
+    panic::set_hook(default_hook);
+
+    assert!(
+        failures.is_empty(),
+        "read_feed panicked on {} of {} documents with PRSS_FUZZ_SEED={}, the first being {}",
+        failures.len(),
+        iterations,
+        seed,
+        failures[0]
+    );
+}
@@ -0,0 +1,47 @@
+use prss::normalize_url;
+
+#[test]
+fn host_and_port() {
+    assert_eq!(
+        normalize_url("HTTPS://Example.COM:443/feed.xml"),
+        "https://example.com/feed.xml"
+    );
+    assert_eq!(
+        normalize_url("http://example.com:80/feed.xml"),
+        "http://example.com/feed.xml"
+    );
+    assert_eq!(
+        normalize_url("http://example.com:8080/feed.xml"),
+        "http://example.com:8080/feed.xml"
+    );
+}
+
+#[test]
+fn query_and_fragment() {
+    assert_eq!(
+        normalize_url("https://example.com/feed?b=2&a=1#top"),
+        "https://example.com/feed?a=1&b=2"
+    );
+    assert_eq!(
+        normalize_url("https://example.com/feed?"),
+        "https://example.com/feed"
+    );
+}
+
+#[test]
+fn path_is_kept() {
+    assert_eq!(normalize_url("https://example.com"), "https://example.com/");
+    assert_ne!(
+        normalize_url("https://example.com/feed"),
+        normalize_url("https://example.com/feed/")
+    );
+    assert_ne!(
+        normalize_url("https://example.com/Feed"),
+        normalize_url("https://example.com/feed")
+    );
+}
+
+#[test]
+fn not_a_url() {
+    assert_eq!(normalize_url("feeds/feed.xml"), "feeds/feed.xml");
+}
@@ -0,0 +1,38 @@
+use std::fs;
+
+use prss::{normalize_url, purge_cache};
+
+#[test]
+fn unsubscribed_feeds_removed() {
+    let dir = std::env::temp_dir().join(format!("prss-purge-{}", std::process::id()));
+    let cache = dir.join("prss");
+    fs::create_dir_all(&cache).unwrap();
+    std::env::set_var("XDG_CACHE_HOME", &dir);
+
+    let name = |url: &str| format!("{:x}", md5::compute(normalize_url(url)));
+    let subscribed = "https://example.com/feed.xml";
+    let compressed = "https://example.com/compressed.xml";
+    fs::write(cache.join(name(subscribed)), "kept").unwrap();
+    fs::write(cache.join(name(compressed) + ".zst"), "kept").unwrap();
+    fs::write(
+        cache.join(name("https://example.com/old.xml") + ".gz"),
+        "gone",
+    )
+    .unwrap();
+    fs::write(cache.join(name(subscribed) + ".1.0.tmp"), "partial").unwrap();
+
+    assert_eq!(
+        purge_cache(&[subscribed.to_string(), compressed.to_string()], None).unwrap(),
+        (2, 11)
+    );
+    let mut left = fs::read_dir(&cache)
+        .unwrap()
+        .map(|f| f.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    left.sort();
+    let mut kept = vec![name(subscribed), name(compressed) + ".zst"];
+    kept.sort();
+    assert_eq!(left, kept);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,470 @@
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use prss::{
+    get_feed_entries, local_feed_path, parse_feed_date, read_feed, read_feed_with_hint, Error,
+    FeedClient, FeedFormat, FetchOptions,
+};
+use reqwest::header::HeaderMap;
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!(
+        "{}/tests/fixtures/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+#[test]
+fn atom() {
+    let feed = read_feed("https://example.com/feed.xml", &fixture("atom.xml")).unwrap();
+
+    assert_eq!(feed.title, "Example Atom Feed");
+    assert_eq!(
+        feed.hub.as_deref(),
+        Some("https://pubsubhubbub.example.com/")
+    );
+    assert_eq!(feed.homepage.as_deref(), Some("https://example.com/"));
+    assert_eq!(feed.entries.len(), 2);
+
+    let entry = &feed.entries[0];
+    assert_eq!(entry.title, "Second post");
+    assert_eq!(entry.url, "https://example.com/second");
+    assert_eq!(entry.date, Utc.ymd(2021, 2, 3).and_hms(9, 0, 0));
+    assert_eq!(entry.author.as_deref(), Some("Jane Doe"));
+
+    assert_eq!(feed.entries[1].author, None);
+}
+
+#[test]
+fn atom_rel_links() {
+    let feed = read_feed(
+        "https://example.com/feed.xml",
+        &fixture("atom_rel_links.xml"),
+    )
+    .unwrap();
+
+    let urls: Vec<_> = feed.entries.iter().map(|e| e.url.as_str()).collect();
+    assert_eq!(
+        urls,
+        [
+            "https://example.com/third",
+            "https://example.com/second",
+            "https://example.com/first"
+        ]
+    );
+    assert_eq!(
+        feed.entries[0].links,
+        ["https://example.com/third", "https://example.com/da/third"]
+    );
+}
+
+#[test]
+fn atom_relative_links() {
+    let feed = read_feed(
+        "https://example.com/feeds/blog.xml",
+        &fixture("atom_relative.xml"),
+    )
+    .unwrap();
+
+    assert_eq!(feed.homepage.as_deref(), Some("https://example.com/blog/"));
+    assert_eq!(
+        feed.next_page.as_deref(),
+        Some("https://example.com/blog/feed.xml?page=2")
+    );
+    let urls: Vec<_> = feed.entries.iter().map(|e| e.url.as_str()).collect();
+    assert_eq!(
+        urls,
+        [
+            "https://example.com/blog/posts/second",
+            "https://example.com/about"
+        ]
+    );
+    assert_eq!(
+        feed.entries[0].comments_url.as_deref(),
+        Some("https://example.com/blog/posts/second#comments")
+    );
+}
+
+#[test]
+fn comments_and_categories() {
+    let feed = read_feed(
+        "https://forum.example.com/feed.xml",
+        &fixture("rss_extras.xml"),
+    )
+    .unwrap();
+    assert_eq!(feed.entries[0].comments, Some(12));
+    assert_eq!(feed.entries[0].categories, ["Rust", "Help"]);
+    assert_eq!(
+        feed.entries[0].comments_url.as_deref(),
+        Some("https://forum.example.com/threads/1#comments")
+    );
+    assert_eq!(feed.entries[0].url, "https://forum.example.com/threads/1");
+    assert_eq!(feed.entries[1].comments, None);
+    assert_eq!(feed.entries[1].comments_url, None);
+    assert!(feed.entries[1].categories.is_empty());
+
+    let feed = read_feed(
+        "https://blog.example.com/feed.xml",
+        &fixture("atom_extras.xml"),
+    )
+    .unwrap();
+    assert_eq!(feed.entries[0].comments, Some(3));
+    assert_eq!(feed.entries[0].categories, ["Rust", "linux"]);
+    assert_eq!(
+        feed.entries[0].comments_url.as_deref(),
+        Some("https://blog.example.com/posts/1#comments")
+    );
+    assert_eq!(feed.entries[0].links, ["https://blog.example.com/posts/1"]);
+}
+
+#[test]
+fn rdf() {
+    let feed = read_feed("https://old.example.org/index.rdf", &fixture("rdf.xml")).unwrap();
+
+    assert_eq!(feed.title, "Example RDF Feed");
+    assert_eq!(feed.homepage.as_deref(), Some("https://old.example.org/"));
+    assert!(feed.warnings.is_empty(), "{:?}", feed.warnings);
+    assert_eq!(feed.entries.len(), 2);
+
+    let entry = &feed.entries[0];
+    assert_eq!(entry.title, "Second item");
+    assert_eq!(entry.url, "https://old.example.org/2");
+    assert_eq!(entry.author.as_deref(), Some("Jane Doe"));
+    assert_eq!(entry.date, Utc.ymd(2021, 1, 12).and_hms(7, 30, 0));
+    assert_eq!(feed.entries[1].date, Utc.ymd(2021, 1, 11).and_hms(0, 0, 0));
+}
+
+#[test]
+fn atom_media() {
+    let feed = read_feed(
+        "https://video.example.com/feed.xml",
+        &fixture("atom_media.xml"),
+    )
+    .unwrap();
+
+    let images: Vec<_> = feed
+        .entries
+        .iter()
+        .map(|e| e.image_url.as_deref())
+        .collect();
+    assert_eq!(
+        images,
+        [
+            Some("https://img.example.com/1/hq.jpg"),
+            Some("https://video.example.com/images/2.png")
+        ]
+    );
+}
+
+#[test]
+fn rss() {
+    let feed = read_feed("https://example.org/feed.xml", &fixture("rss.xml")).unwrap();
+
+    assert_eq!(feed.title, "Example RSS Feed");
+    assert_eq!(feed.hub, None);
+    assert_eq!(feed.homepage.as_deref(), Some("https://example.org/"));
+    assert_eq!(feed.description.as_deref(), Some("An example RSS 2.0 feed"));
+    assert_eq!(feed.entries.len(), 2);
+
+    let entry = &feed.entries[0];
+    assert_eq!(entry.title, "Hello world");
+    assert_eq!(entry.url, "https://example.org/hello");
+    assert_eq!(entry.date, Utc.ymd(2021, 2, 3).and_hms(10, 0, 0));
+    assert_eq!(entry.author.as_deref(), Some("John Smith"));
+
+    let entry = &feed.entries[1];
+    assert_eq!(entry.date, Utc.ymd(2021, 2, 2).and_hms(8, 30, 0));
+    assert_eq!(entry.author.as_deref(), Some("jane@example.org (Jane Doe)"));
+}
+
+#[test]
+fn rss_utc_date() {
+    let feed = read_feed("https://example.net/feed.xml", &fixture("rss_utc_date.xml")).unwrap();
+
+    assert_eq!(
+        feed.entries[0].date,
+        Utc.ymd(2021, 1, 11).and_hms(17, 25, 5)
+    );
+}
+
+#[test]
+fn rss_syndication() {
+    let feed = read_feed(
+        "https://example.net/feed.xml",
+        &fixture("rss_syndication.xml"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        feed.update_interval,
+        Some(Duration::from_secs(12 * 60 * 60))
+    );
+    assert_eq!(
+        read_feed("https://example.org/feed.xml", &fixture("rss.xml"))
+            .unwrap()
+            .update_interval,
+        None
+    );
+
+    // The rss crate panics on an unknown period or frequency.
+    for (valid, invalid) in &[(">daily<", ">fortnightly<"), (">2<", ">twice<")] {
+        let invalid = String::from_utf8(fixture("rss_syndication.xml"))
+            .unwrap()
+            .replace(valid, invalid);
+        let feed = read_feed("https://example.net/feed.xml", invalid.as_bytes()).unwrap();
+        assert_eq!(feed.update_interval, None);
+        assert_eq!(feed.entries.len(), 1);
+    }
+    let escaped = String::from_utf8(fixture("rss_syndication.xml"))
+        .unwrap()
+        .replace("syndication/\"", "syndication&#x2F;\"")
+        .replace(">daily<", ">fortnightly<");
+    let feed = read_feed("https://example.net/feed.xml", escaped.as_bytes()).unwrap();
+    assert_eq!(feed.update_interval, None);
+}
+
+#[test]
+fn rss_missing_link() {
+    let feed = read_feed(
+        "https://example.net/feed.xml",
+        &fixture("rss_missing_link.xml"),
+    )
+    .unwrap();
+
+    let urls = feed.entries.iter().map(|e| &e.url).collect::<Vec<_>>();
+    assert_eq!(
+        urls,
+        [
+            "https://example.net/permanently",
+            "https://example.net/somewhere"
+        ]
+    );
+    assert_eq!(
+        feed.warnings,
+        [
+            "Skipped entry \"Nowhere to go\": no link",
+            "Skipped entry \"Sometime\": couldn't parse date Some(\"last tuesday\")"
+        ]
+    );
+}
+
+#[test]
+fn rss_media() {
+    let feed = read_feed(
+        "https://news.example.com/feed.xml",
+        &fixture("rss_media.xml"),
+    )
+    .unwrap();
+
+    let images: Vec<_> = feed
+        .entries
+        .iter()
+        .map(|e| e.image_url.as_deref())
+        .collect();
+    assert_eq!(
+        images,
+        [
+            Some("https://news.example.com/thumbnail.jpg"),
+            Some("https://news.example.com/content.jpg"),
+            Some("https://news.example.com/enclosure.jpg"),
+            None
+        ]
+    );
+}
+
+#[test]
+fn rss_entities() {
+    let feed = read_feed("https://example.org/feed.xml", &fixture("rss_entities.xml")).unwrap();
+
+    assert_eq!(feed.title, "Tom & Jerry's Blog");
+    assert_eq!(feed.entries[0].title, "Rust & You: \"Fearless\" – it's ❤");
+    // Summaries are HTML, so their entities are kept.
+    assert_eq!(
+        feed.entries[0].summary.as_deref(),
+        Some("<p>Tom &amp; Jerry&#39;s Vec&lt;T&gt;</p>")
+    );
+}
+
+#[test]
+fn rss_latin1() {
+    let feed = read_feed("https://example.dk/feed.xml", &fixture("rss_latin1.xml")).unwrap();
+
+    assert_eq!(feed.title, "Café Feed");
+    assert_eq!(feed.entries[0].title, "Rødgrød med fløde");
+}
+
+#[test]
+fn malformed() {
+    let error = read_feed("https://example.com/404", &fixture("malformed.xml"))
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        error.to_string(),
+        "Couldn't read Atom or RSS from url: https://example.com/404"
+    );
+    assert!(matches!(
+        error,
+        Error::UnsupportedFormat {
+            served_as: None,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn content_type_hint() {
+    assert_eq!(
+        FeedFormat::from_content_type("application/atom+xml; charset=utf-8"),
+        Some(FeedFormat::Atom)
+    );
+    assert_eq!(
+        FeedFormat::from_content_type("application/rss+xml"),
+        Some(FeedFormat::Rss)
+    );
+    assert_eq!(FeedFormat::from_content_type("text/xml"), None);
+
+    let feed = read_feed_with_hint(
+        "https://example.com/feed.xml",
+        &fixture("atom.xml"),
+        Some(FeedFormat::Rss),
+    )
+    .unwrap();
+    assert_eq!(feed.title, "Example Atom Feed");
+
+    let error = read_feed_with_hint(
+        "https://example.com/404",
+        &fixture("malformed.xml"),
+        Some(FeedFormat::Rss),
+    )
+    .err()
+    .unwrap();
+    match &error {
+        Error::UnsupportedFormat {
+            served_as: Some(e), ..
+        } => assert!(matches!(
+            **e,
+            Error::Parse {
+                format: FeedFormat::Rss,
+                ..
+            }
+        )),
+        e => panic!("unexpected error: {}", e),
+    }
+    assert!(error.to_string().starts_with(
+        "Couldn't read Atom or RSS from url: https://example.com/404 (served as RSS: "
+    ));
+}
+
+#[test]
+fn parse_feed_date_formats() {
+    let expected = Some(Utc.ymd(2021, 1, 11).and_hms(17, 25, 5));
+
+    assert_eq!(parse_feed_date("Mon, 11 Jan 2021 17:25:05 +0000"), expected);
+    assert_eq!(parse_feed_date("Mon, 11 Jan 2021 18:25:05 +0100"), expected);
+    assert_eq!(parse_feed_date("Mon, 11 Jan 2021 17:25:05 GMT"), expected);
+    assert_eq!(parse_feed_date("Mon, 11 Jan 2021 17:25:05 UTC"), expected);
+    assert_eq!(
+        parse_feed_date(" Mon, 11 Jan 2021 17:25:05 UTC\n"),
+        expected
+    );
+    assert_eq!(parse_feed_date("2021-01-11T17:25:05Z"), expected);
+    assert_eq!(parse_feed_date("2021-01-11T19:25:05+02:00"), expected);
+    assert_eq!(
+        parse_feed_date("2021-01-11T17:25Z"),
+        Some(Utc.ymd(2021, 1, 11).and_hms(17, 25, 0))
+    );
+    assert_eq!(
+        parse_feed_date("2021-01-11"),
+        Some(Utc.ymd(2021, 1, 11).and_hms(0, 0, 0))
+    );
+}
+
+#[test]
+fn parse_feed_date_invalid() {
+    assert_eq!(parse_feed_date(""), None);
+    assert_eq!(parse_feed_date("yesterday"), None);
+    assert_eq!(parse_feed_date("2021-13-45"), None);
+}
+
+#[test]
+fn local_feed_paths() {
+    assert_eq!(
+        local_feed_path("file:///home/me/feed.xml"),
+        Some("/home/me/feed.xml".into())
+    );
+    assert_eq!(
+        local_feed_path("feeds/feed.xml"),
+        Some("feeds/feed.xml".into())
+    );
+    assert_eq!(
+        local_feed_path("/home/me/feed.xml"),
+        Some("/home/me/feed.xml".into())
+    );
+    assert_eq!(local_feed_path("https://example.com/feed.xml"), None);
+}
+
+#[tokio::test]
+async fn local_feed() {
+    let url = format!(
+        "file://{}/tests/fixtures/rss.xml",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let feed = get_feed_entries(
+        &FeedClient::new(1),
+        &url,
+        &HeaderMap::new(),
+        &FetchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(feed.url, url);
+    assert_eq!(feed.title, "Example RSS Feed");
+}
+
+async fn get_local_feed(name: &str, options: FetchOptions) -> prss::Feed {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    get_feed_entries(&FeedClient::new(1), &path, &HeaderMap::new(), &options)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn next_pages() {
+    let urls = |feed: &prss::Feed| {
+        feed.entries
+            .iter()
+            .map(|e| e.url.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let feed = get_local_feed("atom_page1.xml", FetchOptions::default()).await;
+    assert_eq!(feed.entries.len(), 2);
+
+    // The second page links back to the first, which is not read again.
+    let options = FetchOptions {
+        next_pages: 5,
+        ..FetchOptions::default()
+    };
+    let feed = get_local_feed("atom_page1.xml", options).await;
+    assert_eq!(
+        urls(&feed),
+        [
+            "https://example.com/page1/newer",
+            "https://example.com/page1/older",
+            "https://example.com/page2/newer",
+            "https://example.com/page2/older",
+        ]
+    );
+
+    let options = FetchOptions {
+        next_pages: 5,
+        max_entries: Some(3),
+        ..FetchOptions::default()
+    };
+    let feed = get_local_feed("atom_page1.xml", options).await;
+    assert_eq!(feed.entries.len(), 3);
+}